@@ -0,0 +1,7 @@
+pub mod timestamp;
+
+#[cfg(target_os = "espidf")]
+mod sntp;
+
+#[cfg(target_os = "espidf")]
+pub use sntp::Clock;
@@ -0,0 +1,43 @@
+use std::time::SystemTime;
+
+use esp_idf_hal::sys::EspError;
+use esp_idf_svc::sntp::{EspSntp, SyncStatus};
+
+use super::timestamp::Timestamp;
+
+/// Keeps the system time synchronized over SNTP and tracks whether it is valid.
+pub struct Clock {
+    sntp: EspSntp<'static>,
+    synced: bool,
+}
+
+impl Clock {
+    /// Starts SNTP synchronization in the background, needs a network connection.
+    pub fn start() -> Result<Self, EspError> {
+        log::info!("Starting SNTP time synchronization");
+
+        Ok(Self {
+            sntp: EspSntp::new_default()?,
+            synced: false,
+        })
+    }
+
+    /// Whether the system time has been synchronized since boot.
+    pub fn is_synced(&mut self) -> bool {
+        // ESP-IDF resets the status once it has been read, so it has to be latched
+        if !self.synced && self.sntp.get_sync_status() == SyncStatus::Completed {
+            self.synced = true;
+            log::info!("System time synchronized: {:?}", self.now());
+        }
+
+        self.synced
+    }
+
+    /// Current time, `None` while the clock is not valid.
+    ///
+    /// The time kept by the RTC across a software reset is accepted before
+    /// the first synchronization if it is plausible.
+    pub fn now(&self) -> Option<Timestamp> {
+        Timestamp::from_system_time(SystemTime::now())
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Clocks reporting an earlier time (2024-01-01T00:00:00Z) have not been synchronized yet.
+const MIN_VALID_UNIX_SECS: u64 = 1_704_067_200;
+
+/// Wall-clock time of a reading, in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    pub fn from_unix_millis(millis: u64) -> Self {
+        Self(millis)
+    }
+
+    /// Reads the system time, `None` if it is obviously not set yet.
+    pub fn from_system_time(time: SystemTime) -> Option<Self> {
+        let millis = time.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64;
+        let timestamp = Self(millis);
+
+        timestamp.is_plausible().then_some(timestamp)
+    }
+
+    pub fn unix_millis(&self) -> u64 {
+        self.0
+    }
+
+    pub fn unix_secs(&self) -> u64 {
+        self.0 / 1000
+    }
+
+    pub fn is_plausible(&self) -> bool {
+        self.unix_secs() >= MIN_VALID_UNIX_SECS
+    }
+
+    /// Formats the timestamp as ISO-8601 in UTC, e.g. `2024-05-01T12:30:00.250Z`.
+    pub fn to_iso8601(&self) -> String {
+        let secs = self.unix_secs();
+        let (year, month, day) = civil_from_days(secs / 86_400);
+        let secs_of_day = secs % 86_400;
+
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day % 3600 / 60,
+            secs_of_day % 60,
+            self.0 % 1000
+        )
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_iso8601())
+    }
+}
+
+/// Converts days since the Unix epoch to a (year, month, day) date.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn iso8601(millis: u64) -> String {
+        Timestamp::from_unix_millis(millis).to_iso8601()
+    }
+
+    #[test]
+    fn formats_known_dates() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(iso8601(1_709_209_800_250), "2024-02-29T12:30:00.250Z");
+        assert_eq!(iso8601(951_782_400_000), "2000-02-29T00:00:00.000Z");
+    }
+
+    #[test]
+    fn skips_february_29_of_2100() {
+        assert_eq!(iso8601(4_107_542_399_000), "2100-02-28T23:59:59.000Z");
+        assert_eq!(iso8601(4_107_542_400_000), "2100-03-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn crosses_the_end_of_a_year() {
+        assert_eq!(iso8601(1_735_689_599_999), "2024-12-31T23:59:59.999Z");
+        assert_eq!(iso8601(1_735_689_600_000), "2025-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn rejects_an_unsynchronized_clock() {
+        let boot_time = UNIX_EPOCH + Duration::from_secs(42);
+        assert_eq!(Timestamp::from_system_time(boot_time), None);
+
+        let cutoff = UNIX_EPOCH + Duration::from_secs(MIN_VALID_UNIX_SECS);
+        assert_eq!(
+            Timestamp::from_system_time(cutoff - Duration::from_millis(1)),
+            None
+        );
+        assert_eq!(
+            Timestamp::from_system_time(cutoff),
+            Some(Timestamp::from_unix_millis(MIN_VALID_UNIX_SECS * 1000))
+        );
+    }
+}
@@ -0,0 +1,472 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde_json::json;
+use toml::Value;
+
+use crate::config_store::ConfigWriter;
+use crate::plant_display::MAX_PLANT_NAME_LEN;
+use crate::publisher::Publisher;
+use crate::sensor::{CalibrationPoint, Quantity};
+use crate::sensor_loop::SensorItem;
+
+const MIN_SAMPLING_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_SAMPLING_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Remote command received on `<base>/cmd/<name>`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `cmd/plant_name`, the payload is the new name. Topics rendered from the
+    /// `{plant}` placeholder keep the old name until the next reboot.
+    SetPlantName(String),
+    /// `cmd/sampling_interval`, the payload is the interval in milliseconds.
+    SetSamplingInterval(Duration),
+    /// `cmd/read`
+    TriggerReading,
+    /// `cmd/refresh_display`
+    RefreshDisplay,
+    /// `cmd/calibrate`, the payload is `dry` or `wet`.
+    StartCalibration(CalibrationPoint),
+    /// `cmd/reboot`
+    Reboot,
+}
+
+impl Command {
+    /// Parses the payload of the command named by the last topic level(s).
+    pub fn parse(name: &str, payload: &[u8]) -> Result<Self, CommandError> {
+        let payload = std::str::from_utf8(payload)
+            .map_err(|_| CommandError::InvalidEncoding(name.to_string()))?
+            .trim();
+
+        let invalid = |reason: &str| CommandError::InvalidPayload {
+            command: name.to_string(),
+            reason: reason.to_string(),
+        };
+
+        match name {
+            "plant_name" => {
+                if payload.is_empty() {
+                    return Err(invalid("plant name must not be empty"));
+                }
+                if payload.chars().count() > MAX_PLANT_NAME_LEN {
+                    return Err(invalid("plant name does not fit on the display"));
+                }
+                Ok(Command::SetPlantName(payload.to_string()))
+            }
+            "sampling_interval" => {
+                let millis = payload
+                    .parse()
+                    .map_err(|_| invalid("expected the interval in milliseconds"))?;
+                let interval = Duration::from_millis(millis);
+                if !(MIN_SAMPLING_INTERVAL..=MAX_SAMPLING_INTERVAL).contains(&interval) {
+                    return Err(invalid("interval must be between 100 ms and 24 h"));
+                }
+                Ok(Command::SetSamplingInterval(interval))
+            }
+            "read" => Ok(Command::TriggerReading),
+            "refresh_display" => Ok(Command::RefreshDisplay),
+            "calibrate" => match payload {
+                "dry" => Ok(Command::StartCalibration(CalibrationPoint::Dry)),
+                "wet" => Ok(Command::StartCalibration(CalibrationPoint::Wet)),
+                _ => Err(invalid("expected `dry` or `wet`")),
+            },
+            "reboot" => Ok(Command::Reboot),
+            _ => Err(CommandError::UnknownCommand(name.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::SetPlantName(_) => "plant_name",
+            Command::SetSamplingInterval(_) => "sampling_interval",
+            Command::TriggerReading => "read",
+            Command::RefreshDisplay => "refresh_display",
+            Command::StartCalibration(_) => "calibrate",
+            Command::Reboot => "reboot",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    InvalidEncoding(String),
+    InvalidPayload {
+        command: String,
+        reason: String,
+    },
+    /// The command was valid but could not be carried out.
+    Failed {
+        command: String,
+        reason: String,
+    },
+}
+
+impl CommandError {
+    /// Name of the command the error belongs to.
+    pub fn command(&self) -> &str {
+        match self {
+            CommandError::UnknownCommand(command)
+            | CommandError::InvalidEncoding(command)
+            | CommandError::InvalidPayload { command, .. }
+            | CommandError::Failed { command, .. } => command,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand(command) => write!(f, "unknown command `{}`", command),
+            CommandError::InvalidEncoding(_) => write!(f, "payload is not valid UTF-8"),
+            CommandError::InvalidPayload { reason, .. } => write!(f, "invalid payload: {}", reason),
+            CommandError::Failed { reason, .. } => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Acknowledgement or error published on `<base>/response/<name>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandResponse {
+    pub command: String,
+    pub result: Result<Option<String>, CommandError>,
+}
+
+impl CommandResponse {
+    pub fn ok(command: &Command, detail: Option<String>) -> Self {
+        Self {
+            command: command.name().to_string(),
+            result: Ok(detail),
+        }
+    }
+
+    pub fn error(error: CommandError) -> Self {
+        Self {
+            command: error.command().to_string(),
+            result: Err(error),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match &self.result {
+            Ok(Some(detail)) => json!({ "status": "ok", "detail": detail }),
+            Ok(None) => json!({ "status": "ok" }),
+            Err(e) => json!({ "status": "error", "error": e.to_string() }),
+        }
+        .to_string()
+    }
+}
+
+/// Extracts the command name from a topic below `command_topic`, e.g. `read` from
+/// `plant-doctor/a1b2c3/cmd/read`.
+pub fn command_name<'a>(command_topic: &str, topic: &'a str) -> Option<&'a str> {
+    topic
+        .strip_prefix(command_topic)?
+        .strip_prefix('/')
+        .filter(|name| !name.is_empty())
+}
+
+/// What commands act on, and what they ask the sensor loop to do once all
+/// commands of a cycle were handled.
+pub struct CommandContext<'a> {
+    pub config: &'a mut dyn ConfigWriter,
+    pub sensors: &'a mut [SensorItem],
+    pub publisher: &'a mut dyn Publisher,
+    pub plant_name: Option<String>,
+    pub sampling_interval: Option<Duration>,
+    /// Take a reading right away and report all of its values.
+    pub read_now: bool,
+    pub refresh_display: bool,
+    pub reboot: bool,
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn new(
+        config: &'a mut dyn ConfigWriter,
+        sensors: &'a mut [SensorItem],
+        publisher: &'a mut dyn Publisher,
+    ) -> Self {
+        Self {
+            config,
+            sensors,
+            publisher,
+            plant_name: None,
+            sampling_interval: None,
+            read_now: false,
+            refresh_display: false,
+            reboot: false,
+        }
+    }
+}
+
+/// Carries out `command`, persisting settings it changes.
+pub fn handle(command: &Command, ctx: &mut CommandContext) -> CommandResponse {
+    let result = match command {
+        Command::SetPlantName(name) => ctx
+            .config
+            .set("plant_display.plant_name", Value::from(name.as_str()))
+            .map(|()| {
+                ctx.plant_name = Some(name.clone());
+                ctx.refresh_display = true;
+                if let Err(e) = ctx.publisher.publish_plant_name(name) {
+                    log::error!("Error publishing plant name: {}", e);
+                }
+                None
+            })
+            .map_err(|e| e.to_string()),
+        Command::SetSamplingInterval(interval) => ctx
+            .config
+            .set(
+                "sensors.interval_ms",
+                Value::from(interval.as_millis() as i64),
+            )
+            .map(|()| {
+                ctx.sampling_interval = Some(*interval);
+                None
+            })
+            .map_err(|e| e.to_string()),
+        Command::TriggerReading => {
+            ctx.read_now = true;
+            Ok(None)
+        }
+        Command::RefreshDisplay => {
+            ctx.refresh_display = true;
+            Ok(None)
+        }
+        Command::StartCalibration(point) => calibrate(ctx.sensors, *point).and_then(|value| {
+            let key = match point {
+                CalibrationPoint::Dry => "calibration.dry_value",
+                CalibrationPoint::Wet => "calibration.wet_value",
+            };
+            ctx.config
+                .set(key, Value::from(i64::from(value)))
+                .map_err(|e| e.to_string())?;
+            Ok(Some(format!("ADC value {}", value)))
+        }),
+        Command::Reboot => {
+            ctx.reboot = true;
+            Ok(None)
+        }
+    };
+
+    match result {
+        Ok(detail) => CommandResponse::ok(command, detail),
+        Err(reason) => CommandResponse::error(CommandError::Failed {
+            command: command.name().to_string(),
+            reason,
+        }),
+    }
+}
+
+/// Calibrates the soil moisture sensor, returning the raw value taken.
+fn calibrate(sensors: &mut [SensorItem], point: CalibrationPoint) -> Result<i16, String> {
+    let (sensor, _) = sensors
+        .iter_mut()
+        .find(|(sensor, _)| sensor.quantities().contains(&Quantity::SoilMoisture))
+        .ok_or_else(|| "no soil moisture sensor".to_string())?;
+
+    sensor.calibrate(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Table;
+
+    use super::*;
+    use crate::config::{CalibrationConfig, ConfigError};
+    use crate::config_store::layers::insert_path;
+    use crate::publisher::memory_publisher::MemoryPublisher;
+    use crate::sensor::{Reading, Sensor, SensorError};
+
+    #[derive(Default)]
+    struct Overrides(Table);
+
+    impl ConfigWriter for Overrides {
+        fn set(&mut self, path: &str, value: Value) -> Result<(), ConfigError> {
+            insert_path(&mut self.0, path, value);
+            Ok(())
+        }
+    }
+
+    struct SoilSensor;
+
+    impl Sensor for SoilSensor {
+        fn name(&self) -> &'static str {
+            "soil"
+        }
+
+        fn quantities(&self) -> &'static [Quantity] {
+            &[Quantity::SoilMoisture]
+        }
+
+        fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
+            Ok(Vec::new())
+        }
+
+        fn calibrate(&mut self, point: CalibrationPoint) -> Result<i16, String> {
+            Ok(match point {
+                CalibrationPoint::Dry => 2500,
+                CalibrationPoint::Wet => 900,
+            })
+        }
+    }
+
+    /// Measures 3000 at either point, drier than the default dry value.
+    struct DrySoilSensor;
+
+    impl Sensor for DrySoilSensor {
+        fn name(&self) -> &'static str {
+            "dry soil"
+        }
+
+        fn quantities(&self) -> &'static [Quantity] {
+            &[Quantity::SoilMoisture]
+        }
+
+        fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
+            Ok(Vec::new())
+        }
+
+        fn calibrate(&mut self, point: CalibrationPoint) -> Result<i16, String> {
+            CalibrationConfig::default().with_point(point, 3000)?;
+            Ok(3000)
+        }
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(
+            Command::parse("plant_name", b" Fern \n"),
+            Ok(Command::SetPlantName("Fern".to_string()))
+        );
+        assert_eq!(
+            Command::parse("sampling_interval", b"2000"),
+            Ok(Command::SetSamplingInterval(Duration::from_secs(2)))
+        );
+        assert_eq!(
+            Command::parse("calibrate", b"wet"),
+            Ok(Command::StartCalibration(CalibrationPoint::Wet))
+        );
+        assert_eq!(
+            Command::parse("read", b"ignored"),
+            Ok(Command::TriggerReading)
+        );
+        assert_eq!(Command::parse("reboot", b""), Ok(Command::Reboot));
+    }
+
+    #[test]
+    fn rejects_invalid_commands() {
+        assert_eq!(
+            Command::parse("water", b""),
+            Err(CommandError::UnknownCommand("water".to_string()))
+        );
+        assert_eq!(
+            Command::parse("plant_name", &[0xff]),
+            Err(CommandError::InvalidEncoding("plant_name".to_string()))
+        );
+        for (name, payload) in [
+            ("plant_name", "  "),
+            ("plant_name", "a plant name of 29 characters"),
+            ("sampling_interval", "soon"),
+            ("sampling_interval", "50"),
+            ("sampling_interval", "86400001"),
+            ("calibrate", "damp"),
+        ] {
+            assert!(
+                matches!(
+                    Command::parse(name, payload.as_bytes()),
+                    Err(CommandError::InvalidPayload { .. })
+                ),
+                "{} {:?}",
+                name,
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn extracts_command_names() {
+        assert_eq!(command_name("base/cmd", "base/cmd/read"), Some("read"));
+        assert_eq!(command_name("base/cmd", "base/cmd/"), None);
+        assert_eq!(command_name("base/cmd", "other/cmd/read"), None);
+    }
+
+    #[test]
+    fn renders_responses() {
+        let ok = CommandResponse::ok(&Command::Reboot, None);
+        assert_eq!(ok.to_json(), r#"{"status":"ok"}"#);
+
+        let error = CommandResponse::error(CommandError::UnknownCommand("water".to_string()));
+        assert_eq!(error.command, "water");
+        assert_eq!(
+            error.to_json(),
+            r#"{"error":"unknown command `water`","status":"error"}"#
+        );
+    }
+
+    #[test]
+    fn set_plant_name_stores_and_publishes_it() {
+        let mut config = Overrides::default();
+        let mut publisher = MemoryPublisher::new();
+        let mut ctx = CommandContext::new(&mut config, &mut [], &mut publisher);
+
+        let response = handle(&Command::SetPlantName("Fern".to_string()), &mut ctx);
+
+        assert_eq!(response.result, Ok(None));
+        assert_eq!(ctx.plant_name.as_deref(), Some("Fern"));
+        assert!(ctx.refresh_display);
+        assert_eq!(publisher.plant_names, vec!["Fern".to_string()]);
+        assert_eq!(config.0["plant_display"]["plant_name"], Value::from("Fern"));
+    }
+
+    #[test]
+    fn calibration_stores_the_raw_value() {
+        let mut config = Overrides::default();
+        let mut publisher = MemoryPublisher::new();
+        let mut sensors: Vec<SensorItem> = vec![(Box::new(SoilSensor), Vec::new())];
+        let mut ctx = CommandContext::new(&mut config, &mut sensors, &mut publisher);
+
+        let response = handle(&Command::StartCalibration(CalibrationPoint::Dry), &mut ctx);
+
+        assert_eq!(response.result, Ok(Some("ADC value 2500".to_string())));
+        assert_eq!(config.0["calibration"]["dry_value"], Value::from(2500));
+    }
+
+    #[test]
+    fn calibration_refuses_a_wet_value_above_the_dry_value() {
+        let mut config = Overrides::default();
+        let mut publisher = MemoryPublisher::new();
+        let mut sensors: Vec<SensorItem> = vec![(Box::new(DrySoilSensor), Vec::new())];
+        let mut ctx = CommandContext::new(&mut config, &mut sensors, &mut publisher);
+
+        let response = handle(&Command::StartCalibration(CalibrationPoint::Wet), &mut ctx);
+
+        assert_eq!(
+            response.result,
+            Err(CommandError::Failed {
+                command: "calibrate".to_string(),
+                reason: "dry value 2500 must be above wet value 3000".to_string(),
+            })
+        );
+        assert!(config.0.is_empty());
+    }
+
+    #[test]
+    fn calibration_needs_a_soil_moisture_sensor() {
+        let mut config = Overrides::default();
+        let mut publisher = MemoryPublisher::new();
+        let mut ctx = CommandContext::new(&mut config, &mut [], &mut publisher);
+
+        let response = handle(&Command::StartCalibration(CalibrationPoint::Wet), &mut ctx);
+
+        assert_eq!(
+            response.result,
+            Err(CommandError::Failed {
+                command: "calibrate".to_string(),
+                reason: "no soil moisture sensor".to_string(),
+            })
+        );
+    }
+}
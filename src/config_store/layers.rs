@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use toml::{Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Nvs,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File => write!(f, "config.toml"),
+            ConfigSource::Nvs => write!(f, "nvs"),
+        }
+    }
+}
+
+/// Merges the layers from the lowest to the highest precedence, returning the
+/// merged table and the layer every leaf value came from.
+pub fn merge_layers(
+    defaults: &Table,
+    file: &Table,
+    overrides: &Table,
+) -> (Table, BTreeMap<String, ConfigSource>) {
+    let mut merged = Table::new();
+    let mut sources = BTreeMap::new();
+
+    merge(
+        &mut merged,
+        defaults,
+        ConfigSource::Default,
+        "",
+        &mut sources,
+    );
+    merge(&mut merged, file, ConfigSource::File, "", &mut sources);
+    merge(&mut merged, overrides, ConfigSource::Nvs, "", &mut sources);
+
+    (merged, sources)
+}
+
+/// Deep-merges `layer` into `target`, recording the source of every leaf value.
+fn merge(
+    target: &mut Table,
+    layer: &Table,
+    source: ConfigSource,
+    prefix: &str,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    for (key, value) in layer {
+        let path = join_path(prefix, key);
+
+        if let (Some(Value::Table(existing)), Value::Table(table)) = (target.get_mut(key), value) {
+            merge(existing, table, source, &path, sources);
+            continue;
+        }
+
+        // The whole subtree is replaced, so forget where its previous leaves came from
+        let child_prefix = format!("{}.", path);
+        sources.retain(|p, _| p != &path && !p.starts_with(&child_prefix));
+        record_sources(value, source, &path, sources);
+
+        target.insert(key.clone(), value.clone());
+    }
+}
+
+fn record_sources(
+    value: &Value,
+    source: ConfigSource,
+    path: &str,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                record_sources(value, source, &join_path(path, key), sources);
+            }
+        }
+        _ => {
+            sources.insert(path.to_string(), source);
+        }
+    }
+}
+
+/// Sets the value at a dotted path (e.g. `wifi.ssid`), creating the tables on the way.
+pub fn insert_path(table: &mut Table, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((key, rest)) => {
+            let entry = table
+                .entry(key)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            if let Value::Table(child) = entry {
+                insert_path(child, rest, value);
+            }
+        }
+        None => {
+            table.insert(path.to_string(), value);
+        }
+    }
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> Table {
+        toml.parse().unwrap()
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let defaults = table("[wifi]\nssid = \"default\"\npassword = \"x\"");
+        let file = table("[wifi]\nssid = \"file\"");
+        let overrides = table("[wifi]\npassword = \"nvs\"");
+
+        let (merged, sources) = merge_layers(&defaults, &file, &overrides);
+
+        assert_eq!(merged, table("[wifi]\nssid = \"file\"\npassword = \"nvs\""));
+        assert_eq!(sources["wifi.ssid"], ConfigSource::File);
+        assert_eq!(sources["wifi.password"], ConfigSource::Nvs);
+    }
+
+    #[test]
+    fn replaced_subtree_forgets_its_sources() {
+        let defaults = table("[sensors.overrides.light]\nqos = 1");
+        let file = table("[sensors]\noverrides = 3");
+
+        let (merged, sources) = merge_layers(&defaults, &file, &Table::new());
+
+        assert_eq!(merged, table("[sensors]\noverrides = 3"));
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources["sensors.overrides"], ConfigSource::File);
+    }
+
+    #[test]
+    fn insert_path_creates_tables() {
+        let mut overrides = Table::new();
+        insert_path(&mut overrides, "wifi.static_ip.address", "10.0.0.2".into());
+        insert_path(&mut overrides, "plant_display.plant_name", "Fern".into());
+
+        assert_eq!(
+            overrides,
+            table(
+                "[wifi.static_ip]\naddress = \"10.0.0.2\"\n\n[plant_display]\nplant_name = \"Fern\""
+            )
+        );
+    }
+
+    #[test]
+    fn insert_path_replaces_values_on_the_way() {
+        let mut overrides = table("wifi = \"broken\"");
+        insert_path(&mut overrides, "wifi.ssid", "garden".into());
+
+        assert_eq!(overrides, table("[wifi]\nssid = \"garden\""));
+    }
+}
@@ -0,0 +1,52 @@
+pub mod layers;
+#[cfg(target_os = "espidf")]
+mod store;
+
+use toml::{Table, Value};
+
+use crate::config::ConfigError;
+
+pub use layers::ConfigSource;
+#[cfg(target_os = "espidf")]
+pub use store::ConfigStore;
+
+/// Size of the NVS buffer the overrides are read into, including the NUL terminator.
+pub const MAX_OVERRIDES_LEN: usize = 2048;
+
+/// Serializes the overrides for NVS, rejecting them if they would not fit
+/// into the buffer they are read back into on the next boot.
+pub fn serialize_overrides(overrides: &Table) -> Result<String, ConfigError> {
+    let serialized = toml::to_string(overrides).map_err(ConfigError::Serialize)?;
+    if serialized.len() >= MAX_OVERRIDES_LEN {
+        return Err(ConfigError::OverridesTooLarge(serialized.len()));
+    }
+
+    Ok(serialized)
+}
+
+/// Stores runtime overrides of single config values.
+pub trait ConfigWriter {
+    /// Sets the value at a dotted path, e.g. `sensors.interval_ms`.
+    fn set(&mut self, path: &str, value: Value) -> Result<(), ConfigError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_overrides_too_large_to_read_back() {
+        let mut overrides = Table::new();
+        overrides.insert("plant_name".to_string(), "Monstera".into());
+        assert!(serialize_overrides(&overrides).is_ok());
+
+        overrides.insert(
+            "plant_name".to_string(),
+            "x".repeat(MAX_OVERRIDES_LEN).into(),
+        );
+        assert!(matches!(
+            serialize_overrides(&overrides),
+            Err(ConfigError::OverridesTooLarge(len)) if len > MAX_OVERRIDES_LEN
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+use toml::{Table, Value};
+
+use super::layers::{insert_path, merge_layers, ConfigSource};
+use super::{serialize_overrides, ConfigWriter, MAX_OVERRIDES_LEN};
+use crate::config::{load_config_file, AppConfig, ConfigError};
+
+const NVS_NAMESPACE: &str = "config";
+const OVERRIDES_KEY: &str = "overrides";
+
+/// Values used when neither `config.toml` nor NVS provide them.
+const DEFAULT_CONFIG: &str = r#"
+[plant_display]
+plant_name = "Plant"
+"#;
+
+/// Layers compiled defaults, `config.toml` and runtime overrides stored in NVS
+/// (in this order) into the effective [`AppConfig`].
+pub struct ConfigStore {
+    nvs: EspNvs<NvsDefault>,
+    defaults: Table,
+    file: Table,
+    overrides: Table,
+    merged: Table,
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl ConfigStore {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, ConfigError> {
+        let nvs = EspNvs::new(partition, NVS_NAMESPACE, true).map_err(ConfigError::Nvs)?;
+
+        let defaults = DEFAULT_CONFIG.parse::<Table>()?;
+
+        let file = match load_config_file() {
+            Ok(file) => file,
+            Err(ConfigError::NotFound(path)) => {
+                log::warn!(
+                    "Config file {} not found, using defaults and NVS only",
+                    path
+                );
+                Table::new()
+            }
+            Err(e) => {
+                // A broken file must not keep the device from reaching the provisioning portal
+                log::error!("Ignoring config file: {}", e);
+                Table::new()
+            }
+        };
+
+        let mut buf = vec![0u8; MAX_OVERRIDES_LEN];
+        let overrides = match nvs
+            .get_str(OVERRIDES_KEY, &mut buf)
+            .map_err(ConfigError::Nvs)?
+        {
+            Some(stored) => stored.parse::<Table>().unwrap_or_else(|e| {
+                log::error!("Ignoring stored config overrides: {}", e);
+                Table::new()
+            }),
+            None => Table::new(),
+        };
+
+        let mut store = Self {
+            nvs,
+            defaults,
+            file,
+            overrides,
+            merged: Table::new(),
+            sources: BTreeMap::new(),
+        };
+        store.rebuild();
+
+        Ok(store)
+    }
+
+    /// Returns the effective configuration.
+    pub fn config(&self) -> Result<AppConfig, ConfigError> {
+        AppConfig::from_table(self.merged.clone())
+    }
+
+    /// Returns the layer each effective value came from, keyed by its dotted path.
+    pub fn sources(&self) -> &BTreeMap<String, ConfigSource> {
+        &self.sources
+    }
+
+    /// Stores an override for a dotted path (e.g. `wifi.ssid`) in NVS.
+    pub fn set(&mut self, path: &str, value: impl Into<Value>) -> Result<(), ConfigError> {
+        self.set_all(vec![(path, value.into())])
+    }
+
+    /// Stores several overrides at once.
+    ///
+    /// The overrides are rejected when the resulting configuration would not be
+    /// valid or they would not fit into NVS.
+    pub fn set_all(&mut self, values: Vec<(&str, Value)>) -> Result<(), ConfigError> {
+        let mut overrides = self.overrides.clone();
+        for (path, value) in &values {
+            insert_path(&mut overrides, path, value.clone());
+        }
+
+        let (merged, _) = merge_layers(&self.defaults, &self.file, &overrides);
+        AppConfig::from_table(merged)?;
+        let serialized = serialize_overrides(&overrides)?;
+
+        self.persist(&serialized)?;
+        self.overrides = overrides;
+        self.rebuild();
+
+        for (path, _) in values {
+            log::info!("Stored config override for {}", path);
+        }
+        Ok(())
+    }
+
+    fn persist(&mut self, serialized: &str) -> Result<(), ConfigError> {
+        self.nvs
+            .set_str(OVERRIDES_KEY, serialized)
+            .map_err(ConfigError::Nvs)
+    }
+
+    fn rebuild(&mut self) {
+        (self.merged, self.sources) = merge_layers(&self.defaults, &self.file, &self.overrides);
+    }
+}
+
+impl ConfigWriter for ConfigStore {
+    fn set(&mut self, path: &str, value: Value) -> Result<(), ConfigError> {
+        ConfigStore::set(self, path, value)
+    }
+}
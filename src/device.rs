@@ -0,0 +1,25 @@
+#[cfg(target_os = "espidf")]
+use esp_idf_hal::sys::{esp, esp_efuse_mac_get_default, EspError};
+
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Factory-programmed MAC address of the chip.
+#[cfg(target_os = "espidf")]
+pub fn mac_address() -> Result<[u8; 6], EspError> {
+    let mut mac = [0u8; 6];
+    esp!(unsafe { esp_efuse_mac_get_default(mac.as_mut_ptr()) })?;
+
+    Ok(mac)
+}
+
+/// Short identifier unique to this unit, the last three bytes of the MAC address in hex.
+#[cfg(target_os = "espidf")]
+pub fn device_id() -> Result<String, EspError> {
+    let mac = mac_address()?;
+
+    Ok(format!("{:02x}{:02x}{:02x}", mac[3], mac[4], mac[5]))
+}
+
+pub fn hostname(device_id: &str) -> String {
+    format!("plant-doctor-{}", device_id)
+}
@@ -0,0 +1,125 @@
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::ping::{self, EspPing};
+use esp_idf_svc::wifi::EspWifi;
+
+use super::status::{CheckResult, ConnectivityStatus, HealthMonitor, HealthVerdict};
+use crate::config::HealthCheckConfig;
+
+const BROKER_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Periodically checks the gateway, DNS resolution and the MQTT broker.
+pub struct HealthChecker {
+    config: HealthCheckConfig,
+    broker: Option<(String, u16)>,
+    monitor: HealthMonitor,
+    last_run: Option<Instant>,
+}
+
+impl HealthChecker {
+    pub fn new(config: HealthCheckConfig, broker: Option<(String, u16)>) -> Self {
+        let monitor = HealthMonitor::new(config.alert_after_failures, config.reboot_after_failures);
+
+        Self {
+            config,
+            broker,
+            monitor,
+            last_run: None,
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.monitor.consecutive_failures()
+    }
+
+    /// Runs the checks if the interval has elapsed since the last run.
+    pub fn poll(&mut self, wifi: &EspWifi) -> Option<(ConnectivityStatus, HealthVerdict)> {
+        let interval = Duration::from_secs(self.config.interval_secs);
+        if self
+            .last_run
+            .is_some_and(|last_run| last_run.elapsed() < interval)
+        {
+            return None;
+        }
+        self.last_run = Some(Instant::now());
+
+        let status = self.check(wifi);
+        let verdict = self.monitor.record(&status);
+        log::info!("Connectivity: {:?}, verdict: {:?}", status, verdict);
+
+        Some((status, verdict))
+    }
+
+    fn check(&self, wifi: &EspWifi) -> ConnectivityStatus {
+        let gateway = if self.config.ping_gateway {
+            self.check_gateway(wifi)
+        } else {
+            CheckResult::Skipped
+        };
+
+        // Without a dedicated host, resolving the broker's name doubles as the DNS check
+        let dns_host = self.config.dns_host.as_deref().or(self
+            .broker
+            .as_ref()
+            .map(|(host, _)| host.as_str())
+            .filter(|host| host.parse::<IpAddr>().is_err()));
+        let dns = match dns_host {
+            Some(host) => resolves(host).into(),
+            None => CheckResult::Skipped,
+        };
+
+        let broker = match &self.broker {
+            Some((host, port)) if self.config.check_broker => broker_reachable(host, *port).into(),
+            _ => CheckResult::Skipped,
+        };
+
+        ConnectivityStatus {
+            gateway,
+            broker,
+            dns,
+        }
+    }
+
+    fn check_gateway(&self, wifi: &EspWifi) -> CheckResult {
+        let gateway = match wifi.sta_netif().get_ip_info() {
+            Ok(ip_info) => ip_info.subnet.gateway,
+            Err(e) => {
+                log::warn!("Failed to get IP info: {:?}", e);
+                return CheckResult::Failed;
+            }
+        };
+
+        match EspPing::new(0).ping(gateway, &ping::Configuration::default()) {
+            Ok(summary) => (summary.received > 0).into(),
+            Err(e) => {
+                log::warn!("Ping to gateway {} failed: {:?}", gateway, e);
+                CheckResult::Failed
+            }
+        }
+    }
+}
+
+fn resolves(host: &str) -> bool {
+    match (host, 0).to_socket_addrs() {
+        Ok(mut addrs) => addrs.next().is_some(),
+        Err(e) => {
+            log::warn!("Failed to resolve {}: {:?}", host, e);
+            false
+        }
+    }
+}
+
+fn broker_reachable(host: &str, port: u16) -> bool {
+    let addrs = match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            log::warn!("Failed to resolve broker {}: {:?}", host, e);
+            return false;
+        }
+    };
+
+    addrs
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, BROKER_CONNECT_TIMEOUT).is_ok())
+}
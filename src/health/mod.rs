@@ -0,0 +1,7 @@
+pub mod status;
+
+#[cfg(target_os = "espidf")]
+mod checker;
+
+#[cfg(target_os = "espidf")]
+pub use checker::HealthChecker;
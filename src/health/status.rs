@@ -0,0 +1,203 @@
+use std::fmt;
+
+use serde_json::json;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckResult {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+impl fmt::Display for CheckResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckResult::Ok => write!(f, "ok"),
+            CheckResult::Failed => write!(f, "failed"),
+            CheckResult::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+impl From<bool> for CheckResult {
+    fn from(ok: bool) -> Self {
+        if ok {
+            CheckResult::Ok
+        } else {
+            CheckResult::Failed
+        }
+    }
+}
+
+/// Outcome of one round of connectivity checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectivityStatus {
+    pub gateway: CheckResult,
+    pub broker: CheckResult,
+    pub dns: CheckResult,
+}
+
+impl ConnectivityStatus {
+    pub fn is_healthy(&self) -> bool {
+        [self.gateway, self.broker, self.dns]
+            .iter()
+            .all(|result| *result != CheckResult::Failed)
+    }
+
+    /// Short description of the first failed check, fits on the display.
+    pub fn problem(&self) -> Option<&'static str> {
+        if self.gateway == CheckResult::Failed {
+            Some("no gateway")
+        } else if self.dns == CheckResult::Failed {
+            Some("no DNS")
+        } else if self.broker == CheckResult::Failed {
+            Some("no broker")
+        } else {
+            None
+        }
+    }
+
+    pub fn to_json(&self, consecutive_failures: u32) -> String {
+        json!({
+            "healthy": self.is_healthy(),
+            "gateway": self.gateway.to_string(),
+            "broker": self.broker.to_string(),
+            "dns": self.dns.to_string(),
+            "consecutive_failures": consecutive_failures,
+        })
+        .to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthVerdict {
+    Healthy,
+    /// Some checks failed, but not often enough to alert yet.
+    Degraded,
+    Alert,
+    /// Connectivity did not recover for too long, the device should restart.
+    Reboot,
+}
+
+/// Turns a series of [`ConnectivityStatus`] into alerting and reboot decisions
+/// based on the number of consecutive unhealthy checks.
+pub struct HealthMonitor {
+    alert_after: u32,
+    reboot_after: u32,
+    consecutive_failures: u32,
+}
+
+impl HealthMonitor {
+    /// A threshold of 0 disables the respective verdict.
+    pub fn new(alert_after: u32, reboot_after: u32) -> Self {
+        Self {
+            alert_after,
+            reboot_after,
+            consecutive_failures: 0,
+        }
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    pub fn record(&mut self, status: &ConnectivityStatus) -> HealthVerdict {
+        if status.is_healthy() {
+            self.consecutive_failures = 0;
+            return HealthVerdict::Healthy;
+        }
+
+        self.consecutive_failures += 1;
+
+        if self.reboot_after > 0 && self.consecutive_failures >= self.reboot_after {
+            HealthVerdict::Reboot
+        } else if self.alert_after > 0 && self.consecutive_failures >= self.alert_after {
+            HealthVerdict::Alert
+        } else {
+            HealthVerdict::Degraded
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEALTHY: ConnectivityStatus = ConnectivityStatus {
+        gateway: CheckResult::Ok,
+        broker: CheckResult::Ok,
+        dns: CheckResult::Skipped,
+    };
+    const NO_BROKER: ConnectivityStatus = ConnectivityStatus {
+        gateway: CheckResult::Ok,
+        broker: CheckResult::Failed,
+        dns: CheckResult::Ok,
+    };
+
+    fn verdicts(monitor: &mut HealthMonitor, failures: usize) -> Vec<HealthVerdict> {
+        (0..failures).map(|_| monitor.record(&NO_BROKER)).collect()
+    }
+
+    #[test]
+    fn alerts_and_reboots_after_consecutive_failures() {
+        let mut monitor = HealthMonitor::new(2, 4);
+
+        assert_eq!(
+            verdicts(&mut monitor, 4),
+            vec![
+                HealthVerdict::Degraded,
+                HealthVerdict::Alert,
+                HealthVerdict::Alert,
+                HealthVerdict::Reboot
+            ]
+        );
+        assert_eq!(monitor.consecutive_failures(), 4);
+    }
+
+    #[test]
+    fn success_resets_the_failures() {
+        let mut monitor = HealthMonitor::new(2, 4);
+        verdicts(&mut monitor, 3);
+
+        assert_eq!(monitor.record(&HEALTHY), HealthVerdict::Healthy);
+        assert_eq!(monitor.consecutive_failures(), 0);
+        assert_eq!(monitor.record(&NO_BROKER), HealthVerdict::Degraded);
+    }
+
+    #[test]
+    fn zero_disables_the_thresholds() {
+        let mut no_reboot = HealthMonitor::new(1, 0);
+        assert!(verdicts(&mut no_reboot, 20)
+            .iter()
+            .all(|verdict| *verdict == HealthVerdict::Alert));
+
+        let mut no_alert = HealthMonitor::new(0, 3);
+        assert_eq!(
+            verdicts(&mut no_alert, 3),
+            vec![
+                HealthVerdict::Degraded,
+                HealthVerdict::Degraded,
+                HealthVerdict::Reboot
+            ]
+        );
+    }
+
+    #[test]
+    fn problem_names_the_first_failed_check() {
+        assert_eq!(HEALTHY.problem(), None);
+        assert_eq!(NO_BROKER.problem(), Some("no broker"));
+        let offline = ConnectivityStatus {
+            gateway: CheckResult::Failed,
+            ..NO_BROKER
+        };
+        assert_eq!(offline.problem(), Some("no gateway"));
+    }
+
+    #[test]
+    fn json_snapshot() {
+        assert_eq!(
+            NO_BROKER.to_json(3),
+            r#"{"broker":"failed","consecutive_failures":3,"dns":"ok","gateway":"ok","healthy":false}"#
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+use std::time::Duration;
+
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::io::Write;
+use esp_idf_svc::sys::esp_crt_bundle_attach;
+
+use super::{HttpClient, HttpError};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// [`HttpClient`] on top of the ESP-IDF HTTP client, `https://` URLs are
+/// verified against the built-in CA bundle.
+#[derive(Default)]
+pub struct EspHttpClient;
+
+impl HttpClient for EspHttpClient {
+    fn post(&mut self, url: &str, headers: &[(&str, &str)], body: &[u8]) -> Result<u16, HttpError> {
+        let mut connection = EspHttpConnection::new(&Configuration {
+            timeout: Some(TIMEOUT),
+            crt_bundle_attach: url.starts_with("https://").then_some(esp_crt_bundle_attach),
+            ..Default::default()
+        })
+        .map_err(transport_error)?;
+
+        let content_length = body.len().to_string();
+        let mut all_headers = headers.to_vec();
+        all_headers.push(("Content-Length", &content_length));
+
+        connection
+            .initiate_request(Method::Post, url, &all_headers)
+            .map_err(transport_error)?;
+        connection.write_all(body).map_err(transport_error)?;
+        connection.initiate_response().map_err(transport_error)?;
+
+        match connection.status() {
+            status @ 200..=299 => Ok(status),
+            status => Err(HttpError::Status(status)),
+        }
+    }
+}
+
+fn transport_error(error: impl fmt::Display) -> HttpError {
+    HttpError::Transport(error.to_string())
+}
@@ -0,0 +1,121 @@
+#[cfg(target_os = "espidf")]
+mod esp;
+mod stub;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "espidf")]
+pub use esp::EspHttpClient;
+pub use stub::{StubHttpClient, StubRequest};
+
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// Minimal HTTP client used by the HTTP publishers, so they can run against
+/// [`StubHttpClient`].
+pub trait HttpClient {
+    /// Sends a POST request and returns the response status.
+    fn post(&mut self, url: &str, headers: &[(&str, &str)], body: &[u8]) -> Result<u16, HttpError>;
+}
+
+#[derive(Debug, Clone)]
+pub enum HttpError {
+    /// The request did not reach the server, e.g. it could not be resolved or timed out.
+    Transport(String),
+    /// The server answered with a non-2xx status.
+    Status(u16),
+}
+
+impl HttpError {
+    /// Whether sending the same request again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            HttpError::Transport(_) => true,
+            HttpError::Status(status) => *status == 408 || *status == 429 || *status >= 500,
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Transport(e) => write!(f, "HTTP request failed: {}", e),
+            HttpError::Status(status) => write!(f, "HTTP request failed with status {}", status),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// Exponential backoff between attempts to reach an unavailable server.
+pub struct RetryBackoff {
+    delay: Duration,
+    next_attempt: Option<Instant>,
+}
+
+impl RetryBackoff {
+    pub fn new() -> Self {
+        Self {
+            delay: INITIAL_RETRY_DELAY,
+            next_attempt: None,
+        }
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.next_attempt.map_or(true, |next| now >= next)
+    }
+
+    /// Schedules the next attempt and returns the delay until it.
+    pub fn failed(&mut self, now: Instant) -> Duration {
+        let delay = self.delay;
+        self.next_attempt = Some(now + delay);
+        self.delay = (self.delay * 2).min(MAX_RETRY_DELAY);
+        delay
+    }
+
+    pub fn succeeded(&mut self) {
+        self.delay = INITIAL_RETRY_DELAY;
+        self.next_attempt = None;
+    }
+}
+
+impl Default for RetryBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_errors() {
+        assert!(HttpError::Transport("timed out".to_string()).is_retryable());
+        assert!(HttpError::Status(503).is_retryable());
+        assert!(HttpError::Status(429).is_retryable());
+        assert!(!HttpError::Status(401).is_retryable());
+        assert!(!HttpError::Status(400).is_retryable());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let now = Instant::now();
+        let mut backoff = RetryBackoff::new();
+        assert!(backoff.is_due(now));
+
+        assert_eq!(backoff.failed(now), Duration::from_secs(2));
+        assert!(!backoff.is_due(now + Duration::from_secs(1)));
+        assert!(backoff.is_due(now + Duration::from_secs(2)));
+        assert_eq!(backoff.failed(now), Duration::from_secs(4));
+        for _ in 0..10 {
+            backoff.failed(now);
+        }
+        assert_eq!(backoff.failed(now), MAX_RETRY_DELAY);
+
+        backoff.succeeded();
+        assert!(backoff.is_due(now));
+        assert_eq!(backoff.failed(now), INITIAL_RETRY_DELAY);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::{HttpClient, HttpError};
+
+/// A request recorded by [`StubHttpClient`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StubRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Records requests and answers them from a script, for exercising the HTTP
+/// publishers on the host. Clones share their state, so a test keeps a handle
+/// on the client it gave away.
+#[derive(Clone, Default)]
+pub struct StubHttpClient {
+    state: Arc<Mutex<StubState>>,
+}
+
+#[derive(Default)]
+struct StubState {
+    requests: Vec<StubRequest>,
+    /// Answers to the next requests, `200` once empty.
+    responses: VecDeque<Result<u16, HttpError>>,
+}
+
+impl StubHttpClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues the answer to a later request, after those queued before.
+    pub fn respond_with(&self, response: Result<u16, HttpError>) {
+        self.state.lock().unwrap().responses.push_back(response);
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl HttpClient for StubHttpClient {
+    fn post(&mut self, url: &str, headers: &[(&str, &str)], body: &[u8]) -> Result<u16, HttpError> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(StubRequest {
+            url: url.to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body: String::from_utf8_lossy(body).into_owned(),
+        });
+
+        state.responses.pop_front().unwrap_or(Ok(200))
+    }
+}
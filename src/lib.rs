@@ -0,0 +1,21 @@
+//! Plant doctor firmware. Everything that does not touch the ESP-IDF also
+//! builds for the host, which is where the tests run.
+
+pub mod clock;
+pub mod command;
+pub mod config;
+pub mod config_store;
+pub mod device;
+pub mod health;
+pub mod http_client;
+pub mod image;
+#[cfg(target_os = "espidf")]
+pub mod mdns;
+pub mod metrics;
+pub mod plant_display;
+pub mod provisioning;
+pub mod publisher;
+pub mod sensor;
+pub mod sensor_loop;
+pub mod storage;
+pub mod wifi;
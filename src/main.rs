@@ -1,5 +1,13 @@
 use bh1750::BH1750;
-use config::{load_config, AppConfig};
+use plant_doctor::{
+    clock, command, config, config_store, device, health, http_client, mdns, metrics,
+    plant_display, provisioning, publisher, sensor, sensor_loop, storage, wifi,
+};
+
+use clock::Clock;
+use command::{CommandContext, CommandResponse};
+use config::{parse_broker_url, AppConfig, Backend, MqttLayout, STATE_OVERRIDE};
+use config_store::ConfigStore;
 // use driver::bh1750::BH1750;
 use embedded_hal::delay::DelayNs;
 use embedded_hal::spi::SpiDevice;
@@ -9,10 +17,11 @@ use esp_idf_hal::units::Hertz;
 use sensor::light_intensity_sensor::LightIntensitySensor;
 use sensor::soil_humidity_sensor::SoilMoistureSensor;
 use std::rc::Rc;
-use std::str::FromStr;
 use std::thread;
-use std::time::Duration;
-use wifi::connect_to_wifi;
+use std::time::{Duration, Instant};
+use wifi::create_wifi;
+use wifi::policy::WifiState;
+use wifi::supervisor::WifiSupervisor;
 
 use esp_idf_hal::adc::attenuation::DB_11;
 use esp_idf_hal::adc::oneshot::config::AdcChannelConfig;
@@ -22,58 +31,107 @@ use esp_idf_hal::gpio::{ADCPin, AnyIOPin, Gpio2, InputPin, OutputPin, PinDriver}
 use esp_idf_hal::i2c::{I2c, I2cConfig, I2cDriver};
 use esp_idf_hal::peripheral::Peripheral;
 use esp_idf_hal::peripherals::Peripherals;
-use esp_idf_hal::spi::{config::Config, SpiDeviceDriver};
-use esp_idf_hal::spi::{SpiAnyPins, SpiDriverConfig};
-
-use esp_idf_hal::sys::EspError;
-use esp_idf_svc::ipv4;
-use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration, QoS};
-use esp_idf_svc::ping::EspPing;
+use esp_idf_hal::reset::restart;
+use esp_idf_hal::spi::{config::Config, SpiDeviceDriver, SpiDriverConfig};
+
+use esp_idf_svc::mqtt::client::QoS;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use health::status::HealthVerdict;
+use health::HealthChecker;
+use http_client::EspHttpClient;
+use mdns::Mdns;
+use metrics::Metrics;
 use plant_display::{DisplayInput, PlantDisplay};
-use publisher::sensor_config::SensorConfig;
-use sensor::{test_light_intensity_sensor, test_soil_moisture_sensor, SensorType};
-
-const WET_VALUE: i16 = 900;
-const DRY_VALUE: i16 = 2500;
-
-mod config;
-// mod driver;
-mod image;
-mod plant_display;
-mod publisher;
-mod sensor;
-mod wifi;
-
-pub type SensorItem = (Box<dyn sensor::Sensor>, SensorConfig, SensorType);
+use publisher::buffered_publisher::BufferedPublisher;
+use publisher::discovery::{
+    batched_sensor_discovery, plant_name_discovery, rssi_discovery, sensor_discovery, DeviceInfo,
+};
+use publisher::fan_out::FanOutPublisher;
+use publisher::home_assistant_api::HomeAssistantApiPublisher;
+use publisher::homie;
+use publisher::homie_publisher::HomiePublisher;
+use publisher::influxdb::InfluxDbPublisher;
+use publisher::log_publisher::LogPublisher;
+use publisher::mqtt_connection::{MqttConnection, TopicLayout};
+use publisher::mqtt_publisher::{plant_name_topic, MqttPublisher};
+use publisher::mqtt_security::MqttSecurity;
+use publisher::sensor_config::{render_topic, SensorConfig, TopicVariables};
+use publisher::unavailable_publisher::UnavailablePublisher;
+use publisher::Publisher;
+use sensor::{test_light_intensity_sensor, test_soil_moisture_sensor, Quantity, Sensor};
+use sensor_loop::{CycleInput, ReadSettings, SensorItem, SensorLoop};
+
+/// Failed attempts after which the device falls back to provisioning on boot.
+const MAX_INITIAL_WIFI_FAILURES: u32 = 3;
+/// Time the unused provisioning portal waits before the stored networks are tried again.
+const PROVISIONING_RETRY: Duration = Duration::from_secs(5 * 60);
+const BROKER_DISCOVERY_ATTEMPTS: u32 = 3;
+const BROKER_DISCOVERY_RETRY: Duration = Duration::from_secs(10);
+/// Upper bound of the time between two polls of Wi-Fi, MQTT and commands.
+const LOOP_TICK: Duration = Duration::from_millis(100);
+const REBOOT_DELAY: Duration = Duration::from_secs(1);
 
 fn main() {
     esp_idf_svc::sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    let app_config = load_config().expect("Failed to load configuration");
-    log::info!("Loaded config!");
+    storage::mount().expect("Failed to mount storage partition");
+
+    let nvs = EspDefaultNvsPartition::take().expect("Failed to take NVS partition");
 
     let peripherals = Peripherals::take().unwrap();
 
-    let humidity_sensor = init_soil_humidity_sensor(
+    let device_id = device::device_id().expect("Failed to read MAC address");
+
+    let config_store = ConfigStore::new(nvs.clone()).expect("Failed to open config store");
+    let app_config = match config_store.config() {
+        Ok(app_config) => app_config,
+        Err(e) => {
+            log::error!("Invalid configuration: {}", e);
+            let mut wifi = create_wifi(peripherals.modem, nvs, &device::hostname(&device_id), None)
+                .expect("Failed to create Wi-Fi driver");
+            // Rebooting cannot fix an invalid configuration, only the form can
+            provisioning::run(&mut wifi, config_store, None);
+        }
+    };
+    for (path, source) in config_store.sources() {
+        log::info!("Config {} from {}", path, source);
+    }
+    log::info!("Loaded config!");
+
+    let hostname = app_config
+        .wifi
+        .hostname
+        .clone()
+        .unwrap_or_else(|| device::hostname(&device_id));
+    let wifi = create_wifi(
+        peripherals.modem,
+        nvs,
+        &hostname,
+        app_config.wifi.static_ip.as_ref(),
+    )
+    .expect("Failed to create Wi-Fi driver");
+
+    // The hardware sensors are set up but the test sensors below report instead
+    let _humidity_sensor = init_soil_humidity_sensor(
         peripherals.adc1,
         peripherals.pins.gpio34,
-        WET_VALUE,
-        DRY_VALUE,
+        app_config.calibration.wet_value,
+        app_config.calibration.dry_value,
     )
     .expect("Failed to initialize soil humidity sensor");
 
-    let light_sensor = init_light_sensor(
+    let _light_sensor = init_light_sensor(
         peripherals.i2c0,
         peripherals.pins.gpio21,
         peripherals.pins.gpio22,
     )
-    .unwrap();
+    .expect("Failed to initialize light sensor");
 
     let spi = peripherals.spi2;
     let sclk = peripherals.pins.gpio18;
     let serial_out = peripherals.pins.gpio23;
-    let cs = PinDriver::output(peripherals.pins.gpio15).unwrap();
+    let _cs = PinDriver::output(peripherals.pins.gpio15).unwrap();
     let busy_in = PinDriver::input(peripherals.pins.gpio17).unwrap();
     let dc = PinDriver::output(peripherals.pins.gpio16).unwrap();
     let rst = PinDriver::output(peripherals.pins.gpio4).unwrap();
@@ -91,83 +149,349 @@ fn main() {
     .unwrap();
 
     let mut delay: Delay = Default::default();
-    let mut epd = Epd2in9::new(&mut device, busy_in, dc, rst, &mut delay, None).unwrap();
+    let epd = Epd2in9::new(&mut device, busy_in, dc, rst, &mut delay, None).unwrap();
 
     log::info!("Initializing display");
 
-    let mut display = Display2in9::default();
+    let display = Display2in9::default();
 
     let mut plant_display = PlantDisplay::new(epd, display, delay, device);
 
     let test_light_sensor = test_light_intensity_sensor::TestLightIntensitySensor::new();
     let test_soil_moisture = test_soil_moisture_sensor::TestSoilMoistureSensor::new();
 
-    let sensors: Vec<SensorItem> = vec![
-        (
-            // Box::new(light_sensor),
-            Box::new(test_light_sensor),
-            SensorConfig {
-                topic: "sensor/light_intensity".into(),
-            },
-            SensorType::LightIntensitySensor,
-        ),
-        (
-            // Box::new(humidity_sensor),
-            Box::new(test_soil_moisture),
-            SensorConfig {
-                topic: "sensor/soil_moisture".into(),
-            },
-            SensorType::SoilHumiditySensor,
-        ),
+    let sensors: Vec<Box<dyn Sensor>> = vec![
+        // Box::new(_light_sensor),
+        Box::new(test_light_sensor),
+        // Box::new(_humidity_sensor),
+        Box::new(test_soil_moisture),
     ];
+    let sensors: Vec<SensorItem> = sensors
+        .into_iter()
+        .map(|sensor| {
+            let configs = sensor
+                .quantities()
+                .iter()
+                .map(|quantity| (*quantity, sensor_config(&app_config, &device_id, *quantity)))
+                .collect();
+            (sensor, configs)
+        })
+        .collect();
+
+    let networks = app_config.wifi.known_networks();
+    let max_initial_failures = MAX_INITIAL_WIFI_FAILURES.max(networks.len() as u32);
+    let mut wifi_supervisor = WifiSupervisor::new(wifi, networks).expect("Failed to start Wi-Fi");
+
+    if let Err(e) = wifi_supervisor.wait_connected(max_initial_failures) {
+        log::error!("Failed to connect to Wi-Fi: {:?}", e);
+        provisioning::run(
+            wifi_supervisor.wifi_mut(),
+            config_store,
+            Some(PROVISIONING_RETRY),
+        );
+    }
 
-    let wifi = connect_to_wifi(
-        &app_config.wifi.ssid,
-        &app_config.wifi.password,
-        peripherals.modem,
+    let clock = Clock::start().expect("Failed to start time synchronization");
+
+    let mdns = Mdns::start(
+        &device_id,
+        &hostname,
+        &app_config.plant_display.plant_name,
+        app_config
+            .metrics
+            .enabled
+            .then_some(app_config.metrics.port),
+    )
+    .expect("Failed to start mDNS");
+
+    let backends = app_config.publisher.backends();
+    let metrics = Metrics::new(
+        quantity_configs(&sensors).map(|(quantity, _)| *quantity),
+        sensors.iter().map(|(sensor, _)| sensor.name()),
+        backends.iter().map(Backend::name),
+    );
+    let _metrics_server = app_config.metrics.enabled.then(|| {
+        metrics::start_server(metrics.clone(), app_config.metrics.port)
+            .expect("Failed to start metrics server")
+    });
+
+    let mut broker = None;
+    let mut publishers: Vec<(&'static str, Box<dyn Publisher>)> = Vec::new();
+    for backend in backends {
+        log::info!("Starting {} backend", backend.name());
+        let publisher: Box<dyn Publisher> = match backend {
+            Backend::Mqtt => {
+                let (publisher, broker_url) =
+                    start_mqtt_backend(&app_config, &device_id, &mdns, &sensors);
+                broker = broker_url.as_deref().and_then(parse_broker_url);
+                publisher
+            }
+            Backend::Influxdb => {
+                let config = app_config
+                    .influxdb
+                    .as_ref()
+                    .expect("InfluxDB backend without [influxdb] section");
+                Box::new(InfluxDbPublisher::new(
+                    EspHttpClient,
+                    config,
+                    &device_id,
+                    &app_config.plant_display.plant_name,
+                ))
+            }
+            Backend::HomeAssistantApi => {
+                let home_assistant = &app_config.home_assistant;
+                let publisher = HomeAssistantApiPublisher::new(
+                    EspHttpClient,
+                    home_assistant.api_url.as_deref().unwrap_or_default(),
+                    home_assistant.token.as_deref().unwrap_or_default(),
+                    device_info(&app_config),
+                );
+                Box::new(buffered(publisher, backend, &app_config, &sensors))
+            }
+            Backend::Log => Box::new(LogPublisher),
+        };
+        publishers.push((backend.name(), publisher));
+    }
+    let publisher = FanOutPublisher::new(publishers, metrics.clone());
+
+    let health_checker = HealthChecker::new(app_config.health_check.clone(), broker);
+
+    log::info!("Starting sensor loop");
+
+    run_sensor_loop(
+        publisher,
+        wifi_supervisor,
+        health_checker,
+        clock,
+        plant_display,
+        sensors,
+        config_store,
+        metrics,
+    );
+}
+
+/// Starts the MQTT publisher of the configured layout together with the URL
+/// of its broker. Without a broker or with unusable certificates MQTT stays
+/// unavailable until reboot, so the other backends keep working.
+fn start_mqtt_backend(
+    app_config: &AppConfig,
+    device_id: &str,
+    mdns: &Mdns,
+    sensors: &[SensorItem],
+) -> (Box<dyn Publisher>, Option<String>) {
+    let mqtt_security = match MqttSecurity::load(&app_config.home_assistant) {
+        Ok(mqtt_security) => mqtt_security,
+        Err(e) => {
+            log::error!(
+                "Invalid MQTT security settings, MQTT stays unavailable until reboot: {}",
+                e
+            );
+            return (Box::new(UnavailablePublisher), None);
+        }
+    };
+
+    let broker_url = if app_config.home_assistant.url.is_empty() {
+        discover_broker(mdns)
+    } else {
+        Some(app_config.home_assistant.url.clone())
+    };
+    let Some(broker_url) = broker_url else {
+        log::error!("No MQTT broker found, MQTT stays unavailable until reboot");
+        return (Box::new(UnavailablePublisher), None);
+    };
+
+    let publisher: Box<dyn Publisher> = match app_config.home_assistant.layout {
+        MqttLayout::HomeAssistant => Box::new(start_mqtt(
+            app_config,
+            device_id,
+            &broker_url,
+            mqtt_security,
+            sensors,
+        )),
+        MqttLayout::Homie => Box::new(start_homie(
+            app_config,
+            device_id,
+            &broker_url,
+            mqtt_security,
+            sensors,
+        )),
+    };
+    (publisher, Some(broker_url))
+}
+
+/// Connects to the MQTT broker and announces the device and its sensors to Home Assistant.
+fn start_mqtt(
+    app_config: &AppConfig,
+    device_id: &str,
+    broker_url: &str,
+    mqtt_security: MqttSecurity,
+    sensors: &[SensorItem],
+) -> BufferedPublisher<MqttPublisher> {
+    let device_info = device_info(app_config);
+
+    let base_topic = render_topic(
+        &app_config.home_assistant.base_topic,
+        &TopicVariables {
+            device_id,
+            plant: &app_config.plant_display.plant_name,
+            sensor: "",
+        },
+    )
+    .expect("Invalid base topic");
+
+    let state_config =
+        SensorConfig::state(&app_config.sensors.for_sensor(STATE_OVERRIDE), &base_topic)
+            .unwrap_or_else(|e| panic!("Invalid publish settings for state: {}", e));
+
+    let plant_name_topic = plant_name_topic(&base_topic);
+
+    let mut mqtt = MqttConnection::connect(
+        broker_url,
+        mqtt_security,
+        TopicLayout::HomeAssistant {
+            availability_topic: device_info.availability_topic(),
+            base_topic: base_topic.clone(),
+        },
     )
-    .expect("Failed to connect to Wi-Fi");
+    .expect("Failed to create MQTT client");
+    let mqtt_client = mqtt.client();
+
+    log::info!("Publishing Home Assistant discovery");
+    let mut discovery_messages = vec![plant_name_discovery(&device_info, &plant_name_topic)];
+    if app_config.sensors.batched {
+        discovery_messages.extend(quantity_configs(sensors).map(|(quantity, config)| {
+            batched_sensor_discovery(
+                &device_info,
+                quantity,
+                &state_config.topic,
+                &config.availability_topic(),
+            )
+        }));
+        discovery_messages.push(rssi_discovery(&device_info, &state_config.topic));
+    } else {
+        discovery_messages.extend(quantity_configs(sensors).map(|(quantity, config)| {
+            sensor_discovery(
+                &device_info,
+                quantity,
+                &config.topic,
+                &config.availability_topic(),
+            )
+        }));
+    }
+    for message in discovery_messages {
+        if let Err(e) = mqtt_client.publish(
+            &message.topic,
+            QoS::AtLeastOnce,
+            true,
+            message.payload.as_bytes(),
+        ) {
+            log::error!("Error publishing discovery to {}: {:?}", message.topic, e);
+        }
+    }
 
-    let mut ping_configuration = EspPing::new(0);
-    match ping_configuration.ping(
-        ipv4::Ipv4Addr::from_str("192.168.0.83").unwrap(),
-        &esp_idf_svc::ping::Configuration::default(),
+    log::info!("Publishing plant name to MQTT");
+    if let Err(e) = mqtt_client.publish(
+        &plant_name_topic,
+        QoS::AtLeastOnce,
+        true,
+        app_config.plant_display.plant_name.as_bytes(),
     ) {
-        Ok(summary) => log::info!("Ping successful with summary: {:?}", summary),
-        Err(e) => log::error!("Ping failed: {:?}", e),
+        log::error!(
+            "Error publishing plant name to {}: {:?}",
+            plant_name_topic,
+            e
+        );
     }
 
-    let mut mqtt_client = EspMqttClient::new_cb(
-        &app_config.home_assistant.url,
-        &MqttClientConfiguration {
-            network_timeout: Duration::from_secs(5),
-            ..Default::default()
-        },
-        |event| {
-            log::info!("MQTT Event: {:?}", event.payload());
+    buffered(
+        MqttPublisher::new(mqtt, base_topic, state_config),
+        Backend::Mqtt,
+        app_config,
+        sensors,
+    )
+}
+
+/// Connects to the MQTT broker and announces the device following the Homie convention.
+fn start_homie(
+    app_config: &AppConfig,
+    device_id: &str,
+    broker_url: &str,
+    mqtt_security: MqttSecurity,
+    sensors: &[SensorItem],
+) -> BufferedPublisher<HomiePublisher> {
+    let device_topic = homie::device_topic(&device::hostname(device_id));
+
+    let mut mqtt = MqttConnection::connect(
+        broker_url,
+        mqtt_security,
+        TopicLayout::Homie {
+            device_topic: device_topic.clone(),
         },
     )
-    .unwrap();
+    .expect("Failed to create MQTT client");
+    let mqtt_client = mqtt.client();
+
+    log::info!("Publishing Homie description");
+    let quantities: Vec<Quantity> = quantity_configs(sensors)
+        .map(|(quantity, _)| *quantity)
+        .collect();
+    let messages = homie::description_messages(&homie::Description {
+        device_topic: &device_topic,
+        device_name: "Plant doctor",
+        plant_name: &app_config.plant_display.plant_name,
+        sampling_interval: Duration::from_millis(app_config.sensors.interval_ms),
+        quantities: &quantities,
+    });
+    for (topic, payload) in messages {
+        if let Err(e) = mqtt_client.publish(&topic, QoS::AtLeastOnce, true, payload.as_bytes()) {
+            log::error!("Error publishing {}: {:?}", topic, e);
+        }
+    }
 
-    log::info!("Connected to MQTT broker");
+    buffered(
+        HomiePublisher::new(mqtt, device_topic),
+        Backend::Mqtt,
+        app_config,
+        sensors,
+    )
+}
 
-    log::info!("Publishing plant name to MQTT");
-    mqtt_client
-        .publish(
-            "config/plant_name",
-            QoS::AtMostOnce,
-            false,
-            app_config.plant_display.plant_name.as_bytes(),
-        )
-        .unwrap();
+/// Wraps `publisher` so readings it fails to send are kept and forwarded later.
+fn buffered<P: Publisher>(
+    publisher: P,
+    backend: Backend,
+    app_config: &AppConfig,
+    sensors: &[SensorItem],
+) -> BufferedPublisher<P> {
+    let sensor_configs = quantity_configs(sensors).cloned().collect();
+
+    BufferedPublisher::new(
+        publisher,
+        backend.name(),
+        &app_config.buffer,
+        sensor_configs,
+    )
+}
 
-    log::info!("Starting sensor loop");
+/// Publish settings of every quantity measured by the sensors.
+fn quantity_configs(sensors: &[SensorItem]) -> impl Iterator<Item = &(Quantity, SensorConfig)> {
+    sensors.iter().flat_map(|(_, configs)| configs)
+}
 
-    run_sensor_loop(mqtt_client, plant_display, sensors, &app_config);
+fn device_info(app_config: &AppConfig) -> DeviceInfo {
+    DeviceInfo {
+        mac: device::mac_address().expect("Failed to read MAC address"),
+        plant_name: app_config.plant_display.plant_name.clone(),
+        firmware_version: device::FIRMWARE_VERSION.to_string(),
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_sensor_loop(
-    mut mqtt_client: EspMqttClient<'static>,
+    mut publisher: impl Publisher,
+    mut wifi_supervisor: WifiSupervisor,
+    mut health_checker: HealthChecker,
+    mut clock: Clock,
     mut plant_display: PlantDisplay<
         impl SpiDevice,
         impl embedded_hal::digital::InputPin,
@@ -176,47 +500,149 @@ fn run_sensor_loop(
         impl DelayNs,
     >,
     mut sensors: Vec<SensorItem>,
-    app_config: &AppConfig,
+    mut config_store: ConfigStore,
+    metrics: Metrics,
 ) {
+    let app_config = config_store.config().expect("Failed to load config");
+    let mut plant_name = app_config.plant_display.plant_name;
+    let mut sampling_interval = Duration::from_millis(app_config.sensors.interval_ms);
+
+    let mut network_problem = None;
+    let settings = ReadSettings {
+        batched: app_config.sensors.batched,
+        read_retries: app_config.sensors.read_retries,
+        retry_delay: Duration::from_millis(app_config.sensors.retry_delay_ms),
+    };
+    let mut sensor_loop = SensorLoop::new(settings, &sensors);
+    let mut next_reading = Instant::now();
+
     loop {
-        let mut display_input = DisplayInput {
-            plant_name: app_config.plant_display.plant_name.clone(),
-            soil_moisture: 0.0,
-            light_intensity: 0.0,
-        };
+        let wifi_state = wifi_supervisor.poll();
+        publisher.poll();
+
+        if wifi_state == WifiState::Connected {
+            if let Some((status, verdict)) = health_checker.poll(wifi_supervisor.wifi()) {
+                if let Err(e) =
+                    publisher.publish_connectivity(&status, health_checker.consecutive_failures())
+                {
+                    log::error!("Error publishing connectivity status: {}", e);
+                }
+
+                match verdict {
+                    HealthVerdict::Healthy => network_problem = None,
+                    HealthVerdict::Degraded => {}
+                    HealthVerdict::Alert => {
+                        log::error!("Connectivity problem: {:?}", status);
+                        network_problem = status.problem();
+                    }
+                    HealthVerdict::Reboot => {
+                        log::error!("Connectivity did not recover, rebooting");
+                        restart();
+                    }
+                }
+            }
+        }
 
-        for (sensor, config, sensor_type) in sensors.iter_mut() {
-            log::info!("Reading sensor values");
-            let value = sensor
-                .read()
-                .into_iter()
-                .nth(0)
-                .unwrap_or_else(f32::default);
-
-            match sensor_type {
-                SensorType::LightIntensitySensor => {
-                    display_input.light_intensity = value;
+        let commands = publisher.commands();
+        let mut ctx = CommandContext::new(&mut config_store, &mut sensors, &mut publisher);
+        for command in commands {
+            let response = match command {
+                Ok(command) => {
+                    log::info!("Received command {:?}", command);
+                    command::handle(&command, &mut ctx)
                 }
-                SensorType::SoilHumiditySensor => {
-                    display_input.soil_moisture = value;
+                Err(e) => {
+                    log::warn!("Rejected command: {}", e);
+                    CommandResponse::error(e)
                 }
-                _ => {}
+            };
+
+            if let Err(e) = ctx.publisher.respond(&response) {
+                log::error!("Error responding to command {}: {}", response.command, e);
+            }
+        }
+
+        let mut refresh_display = ctx.refresh_display;
+        let report_all = ctx.read_now;
+        let reboot = ctx.reboot;
+        if let Some(name) = ctx.plant_name {
+            plant_name = name;
+        }
+        if let Some(interval) = ctx.sampling_interval {
+            sampling_interval = interval;
+            next_reading = Instant::now() + sampling_interval;
+        }
+        if report_all {
+            next_reading = Instant::now();
+        }
+
+        if reboot {
+            log::info!("Rebooting on request");
+            // Give the MQTT client a moment to send the acknowledgement
+            thread::sleep(REBOOT_DELAY);
+            restart();
+        }
+
+        if Instant::now() >= next_reading {
+            next_reading = Instant::now() + sampling_interval;
+            refresh_display = true;
+
+            if !clock.is_synced() {
+                log::warn!("System time is not synchronized yet");
             }
 
-            match mqtt_client.publish(
-                config.topic.as_str(),
-                QoS::AtMostOnce,
-                false,
-                format!("{{\"value\": {}}}", value).as_bytes(),
-            ) {
-                Ok(id) => log::info!("Published message with id {}", id),
-                Err(e) => log::error!("Error publishing: {:?}", e),
+            let rssi = match wifi_state {
+                WifiState::Connected => wifi::rssi().ok(),
+                _ => None,
+            };
+            let input = CycleInput {
+                report_all,
+                timestamp: clock.now(),
+                rssi,
             };
+            sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input);
+        }
+
+        if refresh_display {
+            plant_display.display_input(&DisplayInput {
+                plant_name: plant_name.clone(),
+                readings: sensor_loop.latest_readings(),
+                unavailable: sensor_loop.unavailable(),
+                wifi_state,
+                network_problem,
+            });
+        }
+
+        thread::sleep(LOOP_TICK.min(next_reading.saturating_duration_since(Instant::now())));
+    }
+}
+
+fn sensor_config(app_config: &AppConfig, device_id: &str, quantity: Quantity) -> SensorConfig {
+    let vars = TopicVariables {
+        device_id,
+        plant: &app_config.plant_display.plant_name,
+        sensor: quantity.key(),
+    };
+
+    SensorConfig::new(&app_config.sensors.for_sensor(quantity.key()), &vars)
+        .unwrap_or_else(|e| panic!("Invalid publish settings for {}: {}", quantity.key(), e))
+}
+
+/// Looks for an MQTT broker over mDNS, `None` if none answered after a few attempts.
+fn discover_broker(mdns: &Mdns) -> Option<String> {
+    for attempt in 1..=BROKER_DISCOVERY_ATTEMPTS {
+        match mdns.discover_broker() {
+            Ok(Some(url)) => return Some(url),
+            Ok(None) => log::warn!("No MQTT broker found over mDNS"),
+            Err(e) => log::error!("MQTT broker discovery failed: {:?}", e),
         }
 
-        plant_display.display_input(&display_input);
-        thread::sleep(Duration::from_millis(500));
+        if attempt < BROKER_DISCOVERY_ATTEMPTS {
+            thread::sleep(BROKER_DISCOVERY_RETRY);
+        }
     }
+
+    None
 }
 
 fn init_light_sensor(
@@ -235,48 +661,6 @@ fn init_light_sensor(
     Ok(sensor)
 }
 
-fn init_display(
-    spi: impl Peripheral<P = impl SpiAnyPins> + 'static,
-    sclk: impl Peripheral<P = impl OutputPin> + 'static,
-    serial_out: impl Peripheral<P = impl OutputPin> + 'static,
-    cs: impl Peripheral<P = impl OutputPin> + 'static,
-    busy_in: impl Peripheral<P = impl InputPin> + 'static,
-    dc: impl Peripheral<P = impl OutputPin> + 'static,
-    rst: impl Peripheral<P = impl OutputPin> + 'static,
-) -> Result<
-    PlantDisplay<
-        impl SpiDevice,
-        impl embedded_hal::digital::InputPin,
-        impl embedded_hal::digital::OutputPin,
-        impl embedded_hal::digital::OutputPin,
-        impl DelayNs,
-    >,
-    EspError,
-> {
-    let config = Config::new().baudrate(112500.into());
-    let mut device = SpiDeviceDriver::new_single(
-        spi,
-        sclk,
-        serial_out,
-        Option::<Gpio2>::None,
-        Option::<AnyIOPin>::None,
-        &SpiDriverConfig::default(),
-        &config,
-    )?;
-
-    let _ = PinDriver::output(cs)?;
-    let busy_in = PinDriver::input(busy_in)?;
-    let dc = PinDriver::output(dc)?;
-    let rst = PinDriver::output(rst)?;
-
-    let mut delay: Delay = Default::default();
-    let epd = Epd2in9::new(&mut device, busy_in, dc, rst, &mut delay, None).unwrap();
-    let display = Display2in9::default();
-    let plant_display = PlantDisplay::new(epd, display, delay, device);
-
-    Ok(plant_display)
-}
-
 fn init_soil_humidity_sensor<
     A: Adc + 'static,
     P: Peripheral<P = A> + 'static,
@@ -0,0 +1,92 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use esp_idf_hal::sys::EspError;
+use esp_idf_svc::mdns::{EspMdns, Interface, Protocol, QueryResult};
+
+use crate::device::FIRMWARE_VERSION;
+
+const BROKER_QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+const MAX_BROKERS: usize = 4;
+
+/// Announces the device as `<hostname>.local` and looks up MQTT brokers.
+pub struct Mdns {
+    mdns: EspMdns,
+}
+
+impl Mdns {
+    /// The device is advertised as `_plant-doctor._tcp` on `http_port`, where
+    /// it serves its metrics. Without it only the host name is announced.
+    pub fn start(
+        device_id: &str,
+        hostname: &str,
+        plant_name: &str,
+        http_port: Option<u16>,
+    ) -> Result<Self, EspError> {
+        let mut mdns = EspMdns::take()?;
+        mdns.set_hostname(hostname)?;
+        mdns.set_instance_name(plant_name)?;
+
+        if let Some(port) = http_port {
+            mdns.add_service(
+                None,
+                "_plant-doctor",
+                "_tcp",
+                port,
+                &[
+                    ("id", device_id),
+                    ("plant", plant_name),
+                    ("version", FIRMWARE_VERSION),
+                    ("path", "/metrics"),
+                ],
+            )?;
+        }
+
+        log::info!("Announcing {}.local over mDNS", hostname);
+        Ok(Self { mdns })
+    }
+
+    /// Finds an MQTT broker announcing `_mqtt._tcp`, returned as `mqtt://<ip>:<port>`.
+    pub fn discover_broker(&self) -> Result<Option<String>, EspError> {
+        log::info!("Looking for an MQTT broker over mDNS");
+
+        let mut results = vec![empty_query_result(); MAX_BROKERS];
+        let found = self.mdns.query_ptr(
+            "_mqtt",
+            "_tcp",
+            BROKER_QUERY_TIMEOUT,
+            MAX_BROKERS,
+            &mut results,
+        )?;
+
+        let broker = results.into_iter().take(found).find_map(|result| {
+            let addr = result
+                .addr
+                .iter()
+                .find(|addr| addr.is_ipv4())
+                .or(result.addr.first())?;
+
+            let url = match addr {
+                IpAddr::V4(ip) => format!("mqtt://{}:{}", ip, result.port),
+                IpAddr::V6(ip) => format!("mqtt://[{}]:{}", ip, result.port),
+            };
+            log::info!("Found MQTT broker {:?} at {}", result.instance_name, url);
+
+            Some(url)
+        });
+
+        Ok(broker)
+    }
+}
+
+fn empty_query_result() -> QueryResult {
+    QueryResult {
+        instance_name: None,
+        hostname: None,
+        port: 0,
+        txt: Vec::new(),
+        addr: Vec::new(),
+        interface: Interface::STA,
+        ip_protocol: Protocol::V4,
+    }
+}
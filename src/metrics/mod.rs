@@ -0,0 +1,165 @@
+pub mod render;
+#[cfg(target_os = "espidf")]
+mod server;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::sensor::Quantity;
+
+#[cfg(target_os = "espidf")]
+pub use server::start_server;
+
+/// Counters and latest values exposed on `/metrics`, shared between the sensor
+/// loop and the HTTP server.
+#[derive(Clone)]
+pub struct Metrics {
+    state: Arc<Mutex<MetricsState>>,
+    boot: Instant,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsState {
+    pub quantities: BTreeMap<&'static str, QuantityMetrics>,
+    pub sensors: BTreeMap<&'static str, SensorMetrics>,
+    pub backends: BTreeMap<&'static str, BackendMetrics>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantityMetrics {
+    pub unit: &'static str,
+    /// `None` until the first successful read and while the sensor is unavailable.
+    pub value: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensorMetrics {
+    /// Failed attempts, retries included.
+    pub read_errors: u64,
+    /// `None` until the sensor was read for the first time.
+    pub available: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BackendMetrics {
+    pub successes: u64,
+    pub failures: u64,
+    /// Failures since the last success.
+    pub consecutive_failures: u64,
+}
+
+impl Metrics {
+    pub fn new(
+        quantities: impl IntoIterator<Item = Quantity>,
+        sensors: impl IntoIterator<Item = &'static str>,
+        backends: impl IntoIterator<Item = &'static str>,
+    ) -> Self {
+        let quantities = quantities
+            .into_iter()
+            .map(|quantity| (quantity.key(), quantity_metrics(quantity)))
+            .collect();
+        let sensors = sensors
+            .into_iter()
+            .map(|sensor| (sensor, SensorMetrics::default()))
+            .collect();
+        let backends = backends
+            .into_iter()
+            .map(|backend| (backend, BackendMetrics::default()))
+            .collect();
+
+        Self {
+            state: Arc::new(Mutex::new(MetricsState {
+                quantities,
+                sensors,
+                backends,
+            })),
+            boot: Instant::now(),
+        }
+    }
+
+    pub fn record_reading(&self, quantity: Quantity, value: f32) {
+        self.update_quantity(quantity, |metrics| metrics.value = Some(value));
+    }
+
+    /// Drops the value of a quantity whose sensor stopped working.
+    pub fn clear_reading(&self, quantity: Quantity) {
+        self.update_quantity(quantity, |metrics| metrics.value = None);
+    }
+
+    pub fn record_read_error(&self, sensor: &'static str) {
+        let mut state = self.state.lock().unwrap();
+        state.sensors.entry(sensor).or_default().read_errors += 1;
+    }
+
+    pub fn record_sensor_available(&self, sensor: &'static str, available: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.sensors.entry(sensor).or_default().available = Some(available);
+    }
+
+    pub fn record_publish(&self, backend: &'static str, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        let backend = state.backends.entry(backend).or_default();
+        if success {
+            backend.successes += 1;
+            backend.consecutive_failures = 0;
+        } else {
+            backend.failures += 1;
+            backend.consecutive_failures += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.boot.elapsed()
+    }
+
+    fn update_quantity(&self, quantity: Quantity, f: impl FnOnce(&mut QuantityMetrics)) {
+        let mut state = self.state.lock().unwrap();
+        let metrics = state
+            .quantities
+            .entry(quantity.key())
+            .or_insert_with(|| quantity_metrics(quantity));
+        f(metrics);
+    }
+}
+
+fn quantity_metrics(quantity: Quantity) -> QuantityMetrics {
+    QuantityMetrics {
+        unit: quantity.unit(),
+        value: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_consecutive_publish_failures() {
+        let metrics = Metrics::new([], [], ["mqtt"]);
+
+        metrics.record_publish("mqtt", false);
+        metrics.record_publish("mqtt", false);
+        assert_eq!(metrics.snapshot().backends["mqtt"].consecutive_failures, 2);
+
+        metrics.record_publish("mqtt", true);
+        let backend = &metrics.snapshot().backends["mqtt"];
+        assert_eq!((backend.successes, backend.failures), (1, 2));
+        assert_eq!(backend.consecutive_failures, 0);
+    }
+
+    #[test]
+    fn cleared_readings_have_no_value() {
+        let metrics = Metrics::new([Quantity::LightIntensity], [], []);
+
+        metrics.record_reading(Quantity::LightIntensity, 120.0);
+        metrics.clear_reading(Quantity::LightIntensity);
+
+        let quantity = &metrics.snapshot().quantities["light_intensity"];
+        assert_eq!((quantity.unit, quantity.value), ("lx", None));
+    }
+}
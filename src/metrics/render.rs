@@ -0,0 +1,243 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use super::MetricsState;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Values read from the system when the metrics are scraped.
+pub struct DeviceMetrics {
+    pub uptime: Duration,
+    pub free_heap: u32,
+    /// `None` while Wi-Fi is not connected.
+    pub rssi: Option<i8>,
+}
+
+/// Renders the metrics in the Prometheus text exposition format.
+pub fn render(state: &MetricsState, device: &DeviceMetrics) -> String {
+    let mut out = String::new();
+
+    header(
+        &mut out,
+        "plant_doctor_sensor_value",
+        "gauge",
+        "Latest value read from the sensor, missing while it is unavailable.",
+    );
+    for (quantity, metrics) in &state.quantities {
+        if let Some(value) = metrics.value {
+            let _ = writeln!(
+                out,
+                "plant_doctor_sensor_value{{quantity=\"{}\",unit=\"{}\"}} {}",
+                escape_label(quantity),
+                escape_label(metrics.unit),
+                format_value(value)
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "plant_doctor_sensor_read_errors_total",
+        "counter",
+        "Failed sensor reads, retries included.",
+    );
+    for (sensor, metrics) in &state.sensors {
+        let _ = writeln!(
+            out,
+            "plant_doctor_sensor_read_errors_total{{sensor=\"{}\"}} {}",
+            escape_label(sensor),
+            metrics.read_errors
+        );
+    }
+
+    header(
+        &mut out,
+        "plant_doctor_sensor_available",
+        "gauge",
+        "Whether the last read of the sensor succeeded.",
+    );
+    for (sensor, metrics) in &state.sensors {
+        if let Some(available) = metrics.available {
+            let _ = writeln!(
+                out,
+                "plant_doctor_sensor_available{{sensor=\"{}\"}} {}",
+                escape_label(sensor),
+                u8::from(available)
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "plant_doctor_publish_total",
+        "counter",
+        "Published messages by backend and result.",
+    );
+    for (backend, metrics) in &state.backends {
+        let _ = writeln!(
+            out,
+            "plant_doctor_publish_total{{backend=\"{}\",result=\"success\"}} {}",
+            escape_label(backend),
+            metrics.successes
+        );
+        let _ = writeln!(
+            out,
+            "plant_doctor_publish_total{{backend=\"{}\",result=\"failure\"}} {}",
+            escape_label(backend),
+            metrics.failures
+        );
+    }
+
+    header(
+        &mut out,
+        "plant_doctor_backend_consecutive_failures",
+        "gauge",
+        "Failed publishes since the last successful one.",
+    );
+    for (backend, metrics) in &state.backends {
+        let _ = writeln!(
+            out,
+            "plant_doctor_backend_consecutive_failures{{backend=\"{}\"}} {}",
+            escape_label(backend),
+            metrics.consecutive_failures
+        );
+    }
+
+    header(
+        &mut out,
+        "plant_doctor_uptime_seconds",
+        "gauge",
+        "Time since boot.",
+    );
+    let _ = writeln!(
+        out,
+        "plant_doctor_uptime_seconds {}",
+        device.uptime.as_secs()
+    );
+
+    header(
+        &mut out,
+        "plant_doctor_free_heap_bytes",
+        "gauge",
+        "Free heap memory.",
+    );
+    let _ = writeln!(out, "plant_doctor_free_heap_bytes {}", device.free_heap);
+
+    if let Some(rssi) = device.rssi {
+        header(
+            &mut out,
+            "plant_doctor_wifi_rssi_dbm",
+            "gauge",
+            "Signal strength of the Wi-Fi access point.",
+        );
+        let _ = writeln!(out, "plant_doctor_wifi_rssi_dbm {}", rssi);
+    }
+
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn format_value(value: f32) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::sensor::Quantity;
+
+    const EXPECTED: &str = "\
+# HELP plant_doctor_sensor_value Latest value read from the sensor, missing while it is unavailable.
+# TYPE plant_doctor_sensor_value gauge
+plant_doctor_sensor_value{quantity=\"soil_moisture\",unit=\"%\"} 41.5
+# HELP plant_doctor_sensor_read_errors_total Failed sensor reads, retries included.
+# TYPE plant_doctor_sensor_read_errors_total counter
+plant_doctor_sensor_read_errors_total{sensor=\"capacitive\"} 0
+plant_doctor_sensor_read_errors_total{sensor=\"dht22\"} 1
+# HELP plant_doctor_sensor_available Whether the last read of the sensor succeeded.
+# TYPE plant_doctor_sensor_available gauge
+plant_doctor_sensor_available{sensor=\"capacitive\"} 1
+plant_doctor_sensor_available{sensor=\"dht22\"} 0
+# HELP plant_doctor_publish_total Published messages by backend and result.
+# TYPE plant_doctor_publish_total counter
+plant_doctor_publish_total{backend=\"mqtt\",result=\"success\"} 1
+plant_doctor_publish_total{backend=\"mqtt\",result=\"failure\"} 1
+# HELP plant_doctor_backend_consecutive_failures Failed publishes since the last successful one.
+# TYPE plant_doctor_backend_consecutive_failures gauge
+plant_doctor_backend_consecutive_failures{backend=\"mqtt\"} 1
+# HELP plant_doctor_uptime_seconds Time since boot.
+# TYPE plant_doctor_uptime_seconds gauge
+plant_doctor_uptime_seconds 61
+# HELP plant_doctor_free_heap_bytes Free heap memory.
+# TYPE plant_doctor_free_heap_bytes gauge
+plant_doctor_free_heap_bytes 120000
+# HELP plant_doctor_wifi_rssi_dbm Signal strength of the Wi-Fi access point.
+# TYPE plant_doctor_wifi_rssi_dbm gauge
+plant_doctor_wifi_rssi_dbm -61
+";
+
+    fn device(rssi: Option<i8>) -> DeviceMetrics {
+        DeviceMetrics {
+            uptime: Duration::from_secs(61),
+            free_heap: 120_000,
+            rssi,
+        }
+    }
+
+    #[test]
+    fn renders_the_exposition_format() {
+        let metrics = Metrics::new(
+            [Quantity::SoilMoisture, Quantity::AirTemperature],
+            ["capacitive", "dht22"],
+            ["mqtt"],
+        );
+        metrics.record_reading(Quantity::SoilMoisture, 41.5);
+        metrics.record_read_error("dht22");
+        metrics.record_sensor_available("capacitive", true);
+        metrics.record_sensor_available("dht22", false);
+        metrics.record_publish("mqtt", true);
+        metrics.record_publish("mqtt", false);
+
+        assert_eq!(render(&metrics.snapshot(), &device(Some(-61))), EXPECTED);
+    }
+
+    #[test]
+    fn omits_the_signal_while_disconnected() {
+        let metrics = Metrics::new([], [], []);
+
+        let rendered = render(&metrics.snapshot(), &device(None));
+
+        assert!(!rendered.contains("plant_doctor_wifi_rssi_dbm"));
+    }
+
+    #[test]
+    fn formats_special_values() {
+        assert_eq!(format_value(f32::NAN), "NaN");
+        assert_eq!(format_value(f32::INFINITY), "+Inf");
+        assert_eq!(format_value(f32::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(-0.5), "-0.5");
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
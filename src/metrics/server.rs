@@ -0,0 +1,33 @@
+use esp_idf_hal::sys::{esp_get_free_heap_size, EspError};
+use esp_idf_svc::http::server::{Configuration as HttpConfiguration, EspHttpServer};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::io::{EspIOError, Write};
+
+use super::render::{self, render, DeviceMetrics};
+use super::Metrics;
+use crate::wifi;
+
+/// Serves the metrics in the Prometheus text format on `http://<device>:<port>/metrics`.
+pub fn start_server(metrics: Metrics, port: u16) -> Result<EspHttpServer<'static>, EspError> {
+    let mut server = EspHttpServer::new(&HttpConfiguration {
+        http_port: port,
+        ..Default::default()
+    })?;
+
+    server.fn_handler("/metrics", Method::Get, move |req| {
+        let device = DeviceMetrics {
+            uptime: metrics.uptime(),
+            free_heap: unsafe { esp_get_free_heap_size() },
+            rssi: wifi::rssi().ok(),
+        };
+        let body = render(&metrics.snapshot(), &device);
+
+        req.into_response(200, None, &[("Content-Type", render::CONTENT_TYPE)])?
+            .write_all(body.as_bytes())?;
+
+        Ok::<(), EspIOError>(())
+    })?;
+
+    log::info!("Serving metrics on port {}", port);
+    Ok(server)
+}
@@ -18,13 +18,20 @@ use epd_waveshare::{
 
 use embedded_graphics::prelude::*;
 
+use crate::sensor::{Quantity, Reading};
+use crate::wifi::policy::WifiState;
+
+/// Longest plant name that fits on the display, in characters.
+pub const MAX_PLANT_NAME_LEN: usize = 28;
+
 pub struct DisplayInput {
     pub plant_name: String,
-    pub soil_moisture: f32,
-    pub light_intensity: f32,
-    // Currently not used :/
-    // pub air_temperature: f32,
-    // pub air_humidity: f32,
+    /// Latest reading of every quantity, quantities not read yet are left out.
+    pub readings: Vec<Reading>,
+    /// Quantities whose sensor failed, shown instead of a stale value.
+    pub unavailable: Vec<Quantity>,
+    pub wifi_state: WifiState,
+    pub network_problem: Option<&'static str>,
 }
 
 pub struct PlantDisplay<SPI, BUSY, DC, RST, DELAY> {
@@ -64,9 +71,24 @@ where
 
         self.draw_text(&input.plant_name, 10, 10);
 
-        self.draw_text(&format!("{:.2}%", input.soil_moisture), 15, 70);
+        for reading in &input.readings {
+            let (x, y) = position(reading.quantity);
+            self.draw_text(&format_reading(reading), x, y);
+        }
+        for quantity in &input.unavailable {
+            let (x, y) = position(*quantity);
+            self.draw_text("unavailable", x, y);
+        }
 
-        self.draw_text(&format!("{:.2} lux", input.light_intensity), 160, 70);
+        match input.wifi_state {
+            WifiState::Connected => {
+                if let Some(problem) = input.network_problem {
+                    self.draw_text(&format!("Network: {}", problem), 10, 100);
+                }
+            }
+            WifiState::Connecting => self.draw_text("Wi-Fi: connecting", 10, 100),
+            WifiState::Disconnected => self.draw_text("Wi-Fi: offline", 10, 100),
+        }
 
         self.epd
             .update_frame(&mut self.device, self.display.buffer(), &mut self.delay)
@@ -97,17 +119,32 @@ where
         self.display.clear(Color::White).unwrap();
     }
 
-    pub fn Black(&mut self) {
+    pub fn fill_black(&mut self) {
         self.display.clear(Color::Black).unwrap();
     }
 
     // TODO: Implement this
-    pub fn display_image<T>(&mut self, binary_image: &[u8], width: u32, height: u32) {
-        let x = Image::new(
+    pub fn display_image<T>(&mut self, binary_image: &[u8], width: u32, _height: u32) {
+        let _image = Image::new(
             &ImageRaw::<BinaryColor, BigEndian>::new(binary_image, width),
             Point::new(0, 0),
         );
 
-        // x.draw(&mut self.display).unwrap();
+        // _image.draw(&mut self.display).unwrap();
     }
 }
+
+/// Where the value of a quantity is drawn.
+fn position(quantity: Quantity) -> (i32, i32) {
+    match quantity {
+        Quantity::AirTemperature => (15, 40),
+        Quantity::AirHumidity => (160, 40),
+        Quantity::SoilMoisture => (15, 70),
+        Quantity::LightIntensity => (160, 70),
+    }
+}
+
+fn format_reading(reading: &Reading) -> String {
+    let separator = if reading.unit() == "%" { "" } else { " " };
+    format!("{:.2}{}{}", reading.value, separator, reading.unit())
+}
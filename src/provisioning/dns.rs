@@ -0,0 +1,136 @@
+use std::net::{Ipv4Addr, UdpSocket};
+
+const HEADER_LEN: usize = 12;
+const TYPE_A: u16 = 1;
+const TTL_SECS: u32 = 60;
+
+/// Answers every DNS query with `ip`, so that phones open the provisioning page
+/// as a captive portal.
+pub fn run_dns_responder(ip: Ipv4Addr) -> std::io::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:53")?;
+    let mut buf = [0u8; 512];
+
+    loop {
+        let (len, client) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) => {
+                log::warn!("DNS receive failed: {:?}", e);
+                continue;
+            }
+        };
+
+        if let Some(response) = build_response(&buf[..len], ip) {
+            if let Err(e) = socket.send_to(&response, client) {
+                log::warn!("DNS reply to {} failed: {:?}", client, e);
+            }
+        }
+    }
+}
+
+/// Builds a reply to a standard single-question query, resolving A records to `ip`.
+pub fn build_response(query: &[u8], ip: Ipv4Addr) -> Option<Vec<u8>> {
+    if query.len() < HEADER_LEN {
+        return None;
+    }
+
+    let is_response = query[2] & 0x80 != 0;
+    let question_count = u16::from_be_bytes([query[4], query[5]]);
+    if is_response || question_count != 1 {
+        return None;
+    }
+
+    // Skip over the labels of the queried name
+    let mut pos = HEADER_LEN;
+    loop {
+        let label_len = *query.get(pos)? as usize;
+        pos += 1;
+        if label_len == 0 {
+            break;
+        }
+        if label_len & 0xC0 != 0 {
+            return None;
+        }
+        pos += label_len;
+    }
+
+    let question_end = pos + 4;
+    if query.len() < question_end {
+        return None;
+    }
+    let query_type = u16::from_be_bytes([query[pos], query[pos + 1]]);
+    let answer_count: u16 = if query_type == TYPE_A { 1 } else { 0 };
+
+    let mut response = Vec::with_capacity(question_end + 16);
+    response.extend_from_slice(&query[..2]);
+    response.extend_from_slice(&[0x81, 0x80]);
+    response.extend_from_slice(&1u16.to_be_bytes());
+    response.extend_from_slice(&answer_count.to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0]);
+    response.extend_from_slice(&query[HEADER_LEN..question_end]);
+
+    if answer_count > 0 {
+        // Name as a pointer to the question, type A, class IN
+        response.extend_from_slice(&[0xC0, 0x0C, 0, 1, 0, 1]);
+        response.extend_from_slice(&TTL_SECS.to_be_bytes());
+        response.extend_from_slice(&4u16.to_be_bytes());
+        response.extend_from_slice(&ip.octets());
+    }
+
+    Some(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: Ipv4Addr = Ipv4Addr::new(192, 168, 71, 1);
+
+    /// Query with ID 0x1234 for `example.com` of the given type, class IN.
+    fn query(query_type: u16) -> Vec<u8> {
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        query.extend_from_slice(b"\x07example\x03com\x00");
+        query.extend_from_slice(&query_type.to_be_bytes());
+        query.extend_from_slice(&[0, 1]);
+        query
+    }
+
+    #[test]
+    fn answers_a_query_with_own_address() {
+        let query = query(TYPE_A);
+        let response = build_response(&query, IP).unwrap();
+
+        assert_eq!(&response[..2], &[0x12, 0x34]);
+        // Response flags, one question and one answer
+        assert_eq!(&response[2..8], &[0x81, 0x80, 0, 1, 0, 1]);
+        assert_eq!(&response[HEADER_LEN..query.len()], &query[HEADER_LEN..]);
+        assert_eq!(&response[response.len() - 4..], &IP.octets());
+        assert_eq!(response.len(), query.len() + 16);
+    }
+
+    #[test]
+    fn answers_other_types_without_records() {
+        let query = query(28);
+        let response = build_response(&query, IP).unwrap();
+
+        assert_eq!(&response[6..8], &[0, 0]);
+        assert_eq!(response.len(), query.len());
+    }
+
+    #[test]
+    fn ignores_malformed_packets() {
+        let query = query(TYPE_A);
+
+        assert_eq!(build_response(&query[..HEADER_LEN - 1], IP), None);
+        // Cut inside the name and inside the question type
+        assert_eq!(build_response(&query[..HEADER_LEN + 4], IP), None);
+        assert_eq!(build_response(&query[..query.len() - 3], IP), None);
+
+        let mut response = query.clone();
+        response[2] |= 0x80;
+        assert_eq!(build_response(&response, IP), None);
+
+        let mut two_questions = query;
+        two_questions[5] = 2;
+        assert_eq!(build_response(&two_questions, IP), None);
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+
+use crate::plant_display::MAX_PLANT_NAME_LEN;
+
+const MAX_SSID_LEN: usize = 32;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 64;
+
+/// Values submitted through the provisioning page.
+#[derive(Debug, PartialEq)]
+pub struct ProvisioningForm {
+    pub ssid: String,
+    pub password: String,
+    pub mqtt_url: Option<String>,
+    pub plant_name: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FormError {
+    InvalidEncoding,
+    MissingField(&'static str),
+    InvalidValue {
+        field: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormError::InvalidEncoding => write!(f, "the form data is not valid"),
+            FormError::MissingField(field) => write!(f, "{} is required", field),
+            FormError::InvalidValue { field, reason } => write!(f, "{} {}", field, reason),
+        }
+    }
+}
+
+impl ProvisioningForm {
+    /// Returns the config overrides (dotted path, value) to store for this form.
+    pub fn overrides(&self) -> Vec<(&'static str, String)> {
+        let mut overrides = vec![
+            ("wifi.ssid", self.ssid.clone()),
+            ("wifi.password", self.password.clone()),
+            ("plant_display.plant_name", self.plant_name.clone()),
+        ];
+
+        if let Some(url) = &self.mqtt_url {
+            overrides.push(("home_assistant.url", url.clone()));
+        }
+
+        overrides
+    }
+}
+
+/// Parses and validates an `application/x-www-form-urlencoded` request body.
+pub fn parse_form(body: &str) -> Result<ProvisioningForm, FormError> {
+    let mut ssid = None;
+    let mut password = None;
+    let mut mqtt_url = None;
+    let mut plant_name = None;
+
+    for pair in body.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = url_decode(value)?;
+
+        match url_decode(key)?.as_str() {
+            "ssid" => ssid = Some(value),
+            "password" => password = Some(value),
+            "mqtt_url" => mqtt_url = Some(value),
+            "plant_name" => plant_name = Some(value),
+            _ => {}
+        }
+    }
+
+    let ssid = ssid
+        .map(|ssid| ssid.trim().to_string())
+        .filter(|ssid| !ssid.is_empty())
+        .ok_or(FormError::MissingField("ssid"))?;
+    if ssid.len() > MAX_SSID_LEN {
+        return Err(FormError::InvalidValue {
+            field: "ssid",
+            reason: "must be at most 32 bytes long",
+        });
+    }
+
+    // An empty password is allowed for open networks
+    let password = password.unwrap_or_default();
+    if !password.is_empty() && !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&password.len()) {
+        return Err(FormError::InvalidValue {
+            field: "password",
+            reason: "must be between 8 and 64 characters long",
+        });
+    }
+
+    let mqtt_url = mqtt_url
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty());
+    if let Some(url) = &mqtt_url {
+        validate_mqtt_url(url)?;
+    }
+
+    let plant_name = plant_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .ok_or(FormError::MissingField("plant_name"))?;
+    if plant_name.chars().count() > MAX_PLANT_NAME_LEN {
+        return Err(FormError::InvalidValue {
+            field: "plant_name",
+            reason: "must fit on the display",
+        });
+    }
+
+    Ok(ProvisioningForm {
+        ssid,
+        password,
+        mqtt_url,
+        plant_name,
+    })
+}
+
+fn validate_mqtt_url(url: &str) -> Result<(), FormError> {
+    let host = url
+        .strip_prefix("mqtt://")
+        .or_else(|| url.strip_prefix("mqtts://"))
+        .ok_or(FormError::InvalidValue {
+            field: "mqtt_url",
+            reason: "must start with mqtt:// or mqtts://",
+        })?;
+
+    let host = host.split(['/', ':']).next().unwrap_or_default();
+    if host.is_empty() {
+        return Err(FormError::InvalidValue {
+            field: "mqtt_url",
+            reason: "must contain a host",
+        });
+    }
+
+    Ok(())
+}
+
+fn url_decode(value: &str) -> Result<String, FormError> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let high = input.next().and_then(hex_value);
+                let low = input.next().and_then(hex_value);
+                match (high, low) {
+                    (Some(high), Some(low)) => bytes.push((high << 4) | low),
+                    _ => return Err(FormError::InvalidEncoding),
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| FormError::InvalidEncoding)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    (byte as char).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Renders the provisioning page, optionally with an error from a previous submission.
+pub fn render_page(error: Option<&str>) -> String {
+    let error = error
+        .map(|error| format!("<p class=\"error\">{}</p>", html_escape(error)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Plant doctor setup</title>
+<style>
+body {{ font-family: sans-serif; max-width: 24em; margin: 2em auto; padding: 0 1em; }}
+label, input {{ display: block; width: 100%; margin-bottom: 0.5em; }}
+.error {{ color: #b00020; }}
+</style>
+</head>
+<body>
+<h1>Plant doctor setup</h1>
+{error}
+<form method="post" action="/save">
+<label>Wi-Fi network<input name="ssid" maxlength="32" required></label>
+<label>Wi-Fi password<input name="password" type="password" maxlength="64"></label>
+<label>MQTT broker URL<input name="mqtt_url" placeholder="mqtt://192.168.0.10:1883"></label>
+<label>Plant name<input name="plant_name" maxlength="{MAX_PLANT_NAME_LEN}" required></label>
+<input type="submit" value="Save and reboot">
+</form>
+</body>
+</html>
+"#
+    )
+}
+
+pub fn render_saved_page() -> String {
+    "<!DOCTYPE html><html><body><h1>Saved</h1><p>The plant doctor is rebooting and will connect to your network.</p></body></html>".to_string()
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_decodes_fields() {
+        let form = parse_form(
+            "ssid=My+Garden&password=p%40ssw0rd%21&mqtt_url=mqtt%3A%2F%2F10.0.0.2&plant_name=Fern",
+        )
+        .unwrap();
+
+        assert_eq!(
+            form,
+            ProvisioningForm {
+                ssid: "My Garden".to_string(),
+                password: "p@ssw0rd!".to_string(),
+                mqtt_url: Some("mqtt://10.0.0.2".to_string()),
+                plant_name: "Fern".to_string(),
+            }
+        );
+        assert_eq!(form.overrides().len(), 4);
+    }
+
+    #[test]
+    fn allows_open_network_and_discovered_broker() {
+        let form = parse_form("ssid=cafe&password=&mqtt_url=+&plant_name=Fern").unwrap();
+
+        assert_eq!(form.password, "");
+        assert_eq!(form.mqtt_url, None);
+        assert!(!form
+            .overrides()
+            .iter()
+            .any(|(path, _)| *path == "home_assistant.url"));
+    }
+
+    #[test]
+    fn requires_ssid_and_plant_name() {
+        assert_eq!(
+            parse_form("ssid=+&plant_name=Fern"),
+            Err(FormError::MissingField("ssid"))
+        );
+        assert_eq!(
+            parse_form("ssid=garden"),
+            Err(FormError::MissingField("plant_name"))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(matches!(
+            parse_form("ssid=garden&password=short&plant_name=Fern"),
+            Err(FormError::InvalidValue {
+                field: "password",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_form("ssid=garden&mqtt_url=http%3A%2F%2Fbroker&plant_name=Fern"),
+            Err(FormError::InvalidValue {
+                field: "mqtt_url",
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_form(&format!("ssid={}&plant_name=Fern", "x".repeat(33))),
+            Err(FormError::InvalidValue { field: "ssid", .. })
+        ));
+        let long_name = "x".repeat(MAX_PLANT_NAME_LEN + 1);
+        assert!(matches!(
+            parse_form(&format!("ssid=garden&plant_name={}", long_name)),
+            Err(FormError::InvalidValue {
+                field: "plant_name",
+                ..
+            })
+        ));
+        assert!(render_page(None).contains("maxlength=\"28\""));
+    }
+
+    #[test]
+    fn rejects_broken_encoding() {
+        assert_eq!(
+            parse_form("ssid=garden%2&plant_name=Fern"),
+            Err(FormError::InvalidEncoding)
+        );
+        assert_eq!(
+            parse_form("ssid=%ff&plant_name=Fern"),
+            Err(FormError::InvalidEncoding)
+        );
+    }
+}
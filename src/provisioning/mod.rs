@@ -0,0 +1,8 @@
+pub mod dns;
+pub mod form;
+
+#[cfg(target_os = "espidf")]
+mod portal;
+
+#[cfg(target_os = "espidf")]
+pub use portal::run;
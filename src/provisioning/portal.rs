@@ -0,0 +1,154 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use esp_idf_hal::reset::restart;
+use esp_idf_hal::sys::EspError;
+use esp_idf_svc::http::server::{Configuration as HttpConfiguration, EspHttpServer};
+use esp_idf_svc::http::Method;
+use esp_idf_svc::io::{EspIOError, Read, Write};
+use esp_idf_svc::wifi::{AccessPointConfiguration, AuthMethod, Configuration, EspWifi};
+
+use super::{dns, form};
+use crate::config_store::ConfigStore;
+
+const AP_SSID: &str = "plant-doctor-setup";
+const MAX_FORM_LEN: usize = 1024;
+
+/// Starts an open access point with a captive portal asking for the Wi-Fi
+/// credentials, MQTT URL and plant name. Saving the form reboots the device.
+///
+/// With `retry_after` the device also reboots once the portal has not been
+/// used for that long, so it tries the stored networks again, e.g. when the
+/// router came up later than the device after a power cut.
+pub fn run(
+    wifi: &mut EspWifi<'static>,
+    config_store: ConfigStore,
+    retry_after: Option<Duration>,
+) -> ! {
+    log::warn!("Entering provisioning mode, connect to Wi-Fi {}", AP_SSID);
+
+    let last_used = Arc::new(Mutex::new(Instant::now()));
+    match start(wifi, config_store, last_used.clone()) {
+        Ok(_server) => loop {
+            thread::sleep(Duration::from_secs(1));
+
+            if retry_after.is_some_and(|timeout| last_used.lock().unwrap().elapsed() >= timeout) {
+                log::warn!("Provisioning portal unused, rebooting to retry the stored networks");
+                restart();
+            }
+        },
+        Err(e) => {
+            log::error!("Failed to start provisioning: {:?}", e);
+            thread::sleep(Duration::from_secs(10));
+            restart();
+        }
+    }
+}
+
+/// `last_used` is set to the time of every request to the portal.
+fn start(
+    wifi: &mut EspWifi<'static>,
+    config_store: ConfigStore,
+    last_used: Arc<Mutex<Instant>>,
+) -> Result<EspHttpServer<'static>, EspError> {
+    // The station may have been started by a failed connection attempt
+    let _ = wifi.stop();
+
+    wifi.set_configuration(&Configuration::AccessPoint(AccessPointConfiguration {
+        ssid: heapless::String::from_str(AP_SSID)
+            .map_err(|_| EspError::from(1).expect("Failed to create string"))?,
+        auth_method: AuthMethod::None,
+        channel: 1,
+        ..Default::default()
+    }))?;
+    wifi.start()?;
+
+    let ip = wifi.ap_netif().get_ip_info()?.ip;
+    log::info!("Provisioning page available at http://{}/", ip);
+
+    thread::spawn(move || {
+        if let Err(e) = dns::run_dns_responder(ip) {
+            log::error!("DNS responder failed: {:?}", e);
+        }
+    });
+
+    let config_store = Arc::new(Mutex::new(config_store));
+    let mut server = EspHttpServer::new(&HttpConfiguration {
+        uri_match_wildcard: true,
+        ..Default::default()
+    })?;
+
+    let save_used = last_used.clone();
+    server.fn_handler("/save", Method::Post, move |mut req| {
+        *save_used.lock().unwrap() = Instant::now();
+
+        let mut body = Vec::new();
+        let mut buf = [0u8; 256];
+        loop {
+            let len = req.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            if body.len() + len > MAX_FORM_LEN {
+                // Saving a truncated form could store a cut-off password or URL
+                req.into_status_response(413)?
+                    .write_all(form::render_page(Some("The form is too large")).as_bytes())?;
+                return Ok(());
+            }
+            body.extend_from_slice(&buf[..len]);
+        }
+
+        let saved = match form::parse_form(&String::from_utf8_lossy(&body)) {
+            Ok(form) => save(&config_store, &form),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match saved {
+            Ok(()) => {
+                req.into_ok_response()?
+                    .write_all(form::render_saved_page().as_bytes())?;
+
+                thread::spawn(|| {
+                    thread::sleep(Duration::from_secs(2));
+                    restart();
+                });
+            }
+            Err(error) => {
+                req.into_status_response(400)?
+                    .write_all(form::render_page(Some(&error)).as_bytes())?;
+            }
+        }
+
+        Ok::<(), EspIOError>(())
+    })?;
+
+    // Captive portal checks request arbitrary URLs, answer all of them with the form
+    server.fn_handler("/*", Method::Get, move |req| {
+        *last_used.lock().unwrap() = Instant::now();
+
+        req.into_ok_response()?
+            .write_all(form::render_page(None).as_bytes())?;
+
+        Ok::<(), EspIOError>(())
+    })?;
+
+    Ok(server)
+}
+
+fn save(config_store: &Mutex<ConfigStore>, form: &form::ProvisioningForm) -> Result<(), String> {
+    let mut config_store = config_store.lock().unwrap();
+
+    config_store
+        .set_all(
+            form.overrides()
+                .into_iter()
+                .map(|(path, value)| (path, value.into()))
+                .collect(),
+        )
+        .map_err(|e| e.to_string())?;
+
+    log::info!("Saved provisioning form for network {}", form.ssid);
+    Ok(())
+}
@@ -0,0 +1,412 @@
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use serde::Deserialize;
+
+use super::state::StateMessage;
+use crate::clock::timestamp::Timestamp;
+use crate::device::FIRMWARE_VERSION;
+use crate::sensor::{Quantity, Reading};
+
+/// Which message to give up when the buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropPolicy {
+    DropOldest,
+    DropNewest,
+}
+
+/// A message that could not be published yet.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BufferedMessage {
+    Reading(Reading),
+    /// A batched state message, replayed as a whole.
+    State(StateMessage),
+}
+
+/// Bounded FIFO of messages that could not be published yet.
+pub struct MessageBuffer {
+    capacity: usize,
+    drop_policy: DropPolicy,
+    messages: VecDeque<BufferedMessage>,
+    dropped: u64,
+}
+
+impl MessageBuffer {
+    pub fn new(capacity: usize, drop_policy: DropPolicy) -> Self {
+        Self {
+            capacity,
+            drop_policy,
+            messages: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Number of messages lost to the drop policy since boot.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Queues `message`, `false` if the drop policy gave it up instead.
+    pub fn push(&mut self, message: BufferedMessage) -> bool {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            return false;
+        }
+
+        if self.messages.len() >= self.capacity {
+            self.dropped += 1;
+            match self.drop_policy {
+                DropPolicy::DropOldest => {
+                    self.messages.pop_front();
+                }
+                DropPolicy::DropNewest => return false,
+            }
+        }
+
+        self.messages.push_back(message);
+        true
+    }
+
+    pub fn front(&self) -> Option<&BufferedMessage> {
+        self.messages.front()
+    }
+
+    pub fn pop_front(&mut self) -> Option<BufferedMessage> {
+        self.messages.pop_front()
+    }
+
+    /// Serializes the buffer, one line per message.
+    pub fn encode(&self) -> String {
+        self.messages.iter().map(encode_message).collect()
+    }
+
+    /// Restores messages written by [`MessageBuffer::encode`], skipping malformed lines.
+    pub fn decode(&mut self, encoded: &str) {
+        for line in encoded.lines() {
+            match decode_message(line) {
+                Some(message) => {
+                    self.push(message);
+                }
+                None => log::warn!("Skipping malformed buffered message: {:?}", line),
+            }
+        }
+    }
+}
+
+/// Renders a reading as `<sensor>\t<value>\t<unix millis or ->` and a state
+/// message as `state\t<seq>\t<unix millis or ->\t<rssi or ->\t<sensor>:<value>,...`.
+fn encode_message(message: &BufferedMessage) -> String {
+    match message {
+        BufferedMessage::Reading(reading) => format!(
+            "{}\t{}\t{}\n",
+            reading.quantity.key(),
+            reading.value,
+            encode_optional(reading.timestamp.map(|timestamp| timestamp.unix_millis()))
+        ),
+        BufferedMessage::State(state) => {
+            let readings: Vec<String> = state
+                .readings
+                .iter()
+                .map(|reading| format!("{}:{}", reading.quantity.key(), reading.value))
+                .collect();
+            format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                STATE_TAG,
+                state.seq,
+                encode_optional(state.timestamp.map(|timestamp| timestamp.unix_millis())),
+                encode_optional(state.rssi),
+                readings.join(",")
+            )
+        }
+    }
+}
+
+const STATE_TAG: &str = "state";
+
+fn encode_optional(value: Option<impl ToString>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
+
+fn decode_optional<T: std::str::FromStr>(field: &str) -> Option<Option<T>> {
+    match field {
+        "-" => Some(None),
+        field => field.parse().ok().map(Some),
+    }
+}
+
+fn decode_message(line: &str) -> Option<BufferedMessage> {
+    let mut fields = line.split('\t');
+    let first = fields.next()?;
+
+    let message = if first == STATE_TAG {
+        BufferedMessage::State(decode_state(&mut fields)?)
+    } else {
+        let quantity = Quantity::from_key(first)?;
+        let value = fields.next()?.parse().ok()?;
+        let timestamp = decode_optional(fields.next()?)?.map(Timestamp::from_unix_millis);
+        BufferedMessage::Reading(Reading {
+            quantity,
+            value,
+            timestamp,
+        })
+    };
+
+    if fields.next().is_some() {
+        return None;
+    }
+    Some(message)
+}
+
+/// The firmware version is not stored, replayed states carry the running one.
+fn decode_state<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Option<StateMessage> {
+    let seq = fields.next()?.parse().ok()?;
+    let timestamp = decode_optional(fields.next()?)?.map(Timestamp::from_unix_millis);
+    let rssi = decode_optional(fields.next()?)?;
+    let readings = fields
+        .next()?
+        .split(',')
+        .filter(|reading| !reading.is_empty())
+        .map(|reading| {
+            let (key, value) = reading.split_once(':')?;
+            Some(Reading {
+                quantity: Quantity::from_key(key)?,
+                value: value.parse().ok()?,
+                timestamp,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(StateMessage {
+        seq,
+        timestamp,
+        firmware_version: FIRMWARE_VERSION,
+        rssi,
+        readings,
+    })
+}
+
+/// Persists a [`MessageBuffer`]. New messages are appended, the file is only
+/// rewritten once messages left the buffer or it grew to twice the capacity
+/// with messages the drop policy already gave up.
+pub struct BufferFile {
+    path: String,
+    /// Messages in the file, including dropped ones.
+    lines: usize,
+}
+
+impl BufferFile {
+    pub fn new(path: String) -> Self {
+        Self { path, lines: 0 }
+    }
+
+    /// Restores the messages of the file into `buffer`.
+    pub fn load(&mut self, buffer: &mut MessageBuffer) -> io::Result<()> {
+        match fs::read_to_string(&self.path) {
+            Ok(encoded) => {
+                self.lines = encoded.lines().count();
+                buffer.decode(&encoded);
+                Ok(())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Adds `messages`, which were just pushed to `buffer`.
+    pub fn append(
+        &mut self,
+        buffer: &MessageBuffer,
+        messages: &[BufferedMessage],
+    ) -> io::Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        if self.lines + messages.len() > 2 * buffer.capacity() {
+            return self.rewrite(buffer);
+        }
+
+        let encoded: String = messages.iter().map(encode_message).collect();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(encoded.as_bytes())?;
+
+        self.lines += messages.len();
+        Ok(())
+    }
+
+    /// Replaces the file with the messages in `buffer`, removing it once empty.
+    pub fn rewrite(&mut self, buffer: &MessageBuffer) -> io::Result<()> {
+        if buffer.is_empty() {
+            match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        } else {
+            fs::write(&self.path, buffer.encode())?;
+        }
+
+        self.lines = buffer.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(value: f32) -> BufferedMessage {
+        BufferedMessage::Reading(Reading {
+            quantity: Quantity::SoilMoisture,
+            value,
+            timestamp: Some(Timestamp::from_unix_millis(1_700_000_000_000)),
+        })
+    }
+
+    fn values(buffer: &mut MessageBuffer) -> Vec<f32> {
+        std::iter::from_fn(|| buffer.pop_front())
+            .map(|message| match message {
+                BufferedMessage::Reading(reading) => reading.value,
+                BufferedMessage::State(_) => panic!("expected a reading"),
+            })
+            .collect()
+    }
+
+    fn pop_reading(buffer: &mut MessageBuffer) -> Reading {
+        match buffer.pop_front() {
+            Some(BufferedMessage::Reading(reading)) => reading,
+            message => panic!("expected a reading, got {:?}", message),
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("plant_doctor_{}_{}.txt", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn drop_policies() {
+        let mut oldest = MessageBuffer::new(2, DropPolicy::DropOldest);
+        let mut newest = MessageBuffer::new(2, DropPolicy::DropNewest);
+        for value in [1.0, 2.0] {
+            assert!(oldest.push(reading(value)));
+            assert!(newest.push(reading(value)));
+        }
+
+        assert!(oldest.push(reading(3.0)));
+        assert!(!newest.push(reading(3.0)));
+        assert_eq!((oldest.dropped(), newest.dropped()), (1, 1));
+        assert_eq!(values(&mut oldest), vec![2.0, 3.0]);
+        assert_eq!(values(&mut newest), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn decode_skips_malformed_lines() {
+        let mut buffer = MessageBuffer::new(10, DropPolicy::DropOldest);
+        buffer.decode("soil_moisture\t41.5\t-\nsoil_moisture\tdry\t-\nsoil_moisture\t42\t1000\n");
+
+        let first = pop_reading(&mut buffer);
+        assert_eq!((first.value, first.timestamp), (41.5, None));
+        let second = pop_reading(&mut buffer);
+        assert_eq!(second.timestamp, Some(Timestamp::from_unix_millis(1000)));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn state_messages_round_trip() {
+        let timestamp = Some(Timestamp::from_unix_millis(1_700_000_000_000));
+        let state = StateMessage {
+            seq: 7,
+            timestamp,
+            firmware_version: FIRMWARE_VERSION,
+            rssi: Some(-61),
+            readings: vec![
+                Reading {
+                    quantity: Quantity::SoilMoisture,
+                    value: 41.5,
+                    timestamp,
+                },
+                Reading {
+                    quantity: Quantity::AirTemperature,
+                    value: -2.25,
+                    timestamp,
+                },
+            ],
+        };
+        let mut buffer = MessageBuffer::new(10, DropPolicy::DropOldest);
+        buffer.push(BufferedMessage::State(state.clone()));
+        buffer.push(reading(3.0));
+
+        let mut restored = MessageBuffer::new(10, DropPolicy::DropOldest);
+        restored.decode(&buffer.encode());
+
+        assert_eq!(restored.pop_front(), Some(BufferedMessage::State(state)));
+        assert_eq!(restored.pop_front(), Some(reading(3.0)));
+    }
+
+    #[test]
+    fn file_appends_and_restores() {
+        let path = temp_path("append");
+        let mut buffer = MessageBuffer::new(4, DropPolicy::DropOldest);
+        let mut file = BufferFile::new(path.clone());
+
+        for value in [1.0, 2.0] {
+            buffer.push(reading(value));
+            file.append(&buffer, &[reading(value)]).unwrap();
+        }
+
+        let mut restored = MessageBuffer::new(4, DropPolicy::DropOldest);
+        BufferFile::new(path.clone()).load(&mut restored).unwrap();
+        assert_eq!(values(&mut restored), vec![1.0, 2.0]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn file_is_compacted_once_it_holds_dropped_readings() {
+        let path = temp_path("compact");
+        let mut buffer = MessageBuffer::new(2, DropPolicy::DropOldest);
+        let mut file = BufferFile::new(path.clone());
+
+        for value in [1.0, 2.0, 3.0, 4.0, 5.0] {
+            buffer.push(reading(value));
+            file.append(&buffer, &[reading(value)]).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        let mut restored = MessageBuffer::new(2, DropPolicy::DropOldest);
+        BufferFile::new(path.clone()).load(&mut restored).unwrap();
+        assert_eq!(values(&mut restored), vec![4.0, 5.0]);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn empty_rewrite_removes_the_file() {
+        let path = temp_path("remove");
+        let mut buffer = MessageBuffer::new(2, DropPolicy::DropOldest);
+        let mut file = BufferFile::new(path.clone());
+        buffer.push(reading(1.0));
+        file.append(&buffer, &[reading(1.0)]).unwrap();
+
+        buffer.pop_front();
+        file.rewrite(&buffer).unwrap();
+
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,373 @@
+use super::buffer::{BufferFile, BufferedMessage, MessageBuffer};
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::config::BufferConfig;
+use crate::health::status::ConnectivityStatus;
+use crate::sensor::{Quantity, Reading};
+use crate::storage;
+
+/// Stores readings and state messages the inner publisher fails to send and
+/// forwards them in order once it works again. The buffer survives reboots on
+/// the storage partition, in a file of its own for every backend.
+pub struct BufferedPublisher<P: Publisher> {
+    inner: P,
+    buffer: MessageBuffer,
+    sensors: Vec<(Quantity, SensorConfig)>,
+    drain_batch: usize,
+    /// `None` unless the buffer is persisted.
+    file: Option<BufferFile>,
+    /// Messages left the buffer since the file was last written.
+    unsaved: bool,
+    /// Messages dropped because the backend refused them.
+    rejected: u64,
+}
+
+impl<P: Publisher> BufferedPublisher<P> {
+    /// `name` identifies the backend, its messages are stored in `buffer_<name>.txt`.
+    pub fn new(
+        inner: P,
+        name: &str,
+        config: &BufferConfig,
+        sensors: Vec<(Quantity, SensorConfig)>,
+    ) -> Self {
+        let mut buffer = MessageBuffer::new(config.capacity, config.drop_policy);
+
+        let file = config.persist.then(|| {
+            let mut file = BufferFile::new(storage::path(&format!("buffer_{}.txt", name)));
+            if let Err(e) = file.load(&mut buffer) {
+                log::error!("Failed to read buffered messages: {:?}", e);
+            }
+            log::info!("Restored {} buffered {} messages", buffer.len(), name);
+            file
+        });
+
+        Self {
+            inner,
+            buffer,
+            sensors,
+            drain_batch: config.drain_batch,
+            file,
+            unsaved: false,
+            rejected: 0,
+        }
+    }
+
+    /// Forwards up to `drain_batch` buffered messages, oldest first. Messages
+    /// the backend refuses are dropped, so they do not block the others.
+    fn drain(&mut self) -> Result<(), PublishError> {
+        let mut drained = 0;
+
+        while drained < self.drain_batch {
+            let result = match self.buffer.front() {
+                None => break,
+                Some(BufferedMessage::State(state)) => self.inner.publish_state(state),
+                Some(BufferedMessage::Reading(reading)) => {
+                    let Some((_, config)) = self
+                        .sensors
+                        .iter()
+                        .find(|(quantity, _)| *quantity == reading.quantity)
+                    else {
+                        log::warn!("Dropping buffered reading of unknown sensor {:?}", reading);
+                        self.buffer.pop_front();
+                        continue;
+                    };
+                    self.inner.publish(config, reading)
+                }
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(e) if e.is_retryable() => return Err(e),
+                Err(e) => self.reject(&e),
+            }
+            self.buffer.pop_front();
+            drained += 1;
+        }
+
+        if drained > 0 {
+            log::info!(
+                "Forwarded {} buffered messages, {} left",
+                drained,
+                self.buffer.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn reject(&mut self, error: &PublishError) {
+        self.rejected += 1;
+        log::error!(
+            "Dropping message refused by the backend ({} so far): {}",
+            self.rejected,
+            error
+        );
+    }
+
+    /// Buffers the message that failed with `result`, unless the backend refused it.
+    fn keep_failed(
+        &mut self,
+        result: &Result<(), PublishError>,
+        message: impl FnOnce() -> BufferedMessage,
+    ) {
+        match result {
+            Ok(()) => {}
+            Err(e) if e.is_retryable() => self.enqueue(message()),
+            Err(e) => self.reject(e),
+        }
+    }
+
+    /// Queues `message` behind those already waiting and appends it to the file.
+    fn enqueue(&mut self, message: BufferedMessage) {
+        if !self.buffer.push(message.clone()) {
+            return;
+        }
+
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.append(&self.buffer, &[message]) {
+                log::error!("Failed to save buffered messages: {:?}", e);
+            }
+        }
+    }
+
+    /// Writes the buffer again after messages were forwarded.
+    fn save(&mut self) {
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.rewrite(&self.buffer) {
+                log::error!("Failed to save buffered messages: {:?}", e);
+            }
+        }
+    }
+
+    /// Forwards buffered messages. The file is only written once the pass
+    /// ends, with an empty buffer or a failure, to spare the flash.
+    fn drain_and_save(&mut self) -> Result<(), PublishError> {
+        let before = self.buffer.len();
+        let result = self.drain();
+        self.unsaved |= self.buffer.len() != before;
+
+        if self.unsaved && (self.buffer.is_empty() || result.is_err()) {
+            self.save();
+            self.unsaved = false;
+        }
+        result
+    }
+}
+
+impl<P: Publisher> Publisher for BufferedPublisher<P> {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        if self.buffer.is_empty() {
+            let result = self.inner.publish(config, reading);
+            self.keep_failed(&result, || BufferedMessage::Reading(reading.clone()));
+            return result;
+        }
+
+        // Older messages are still waiting, queue this one behind them
+        self.enqueue(BufferedMessage::Reading(reading.clone()));
+        let result = self.drain_and_save();
+
+        log::info!("{} messages buffered", self.buffer.len());
+        result
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        if self.buffer.is_empty() {
+            let result = self.inner.publish_state(state);
+            self.keep_failed(&result, || BufferedMessage::State(state.clone()));
+            return result;
+        }
+
+        self.enqueue(BufferedMessage::State(state.clone()));
+        let result = self.drain_and_save();
+
+        log::info!("{} messages buffered", self.buffer.len());
+        result
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        self.inner
+            .publish_connectivity(status, consecutive_failures)
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        self.inner.publish_plant_name(name)
+    }
+
+    fn publish_availability(
+        &mut self,
+        config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        self.inner.publish_availability(config, quantity, available)
+    }
+
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        self.inner.commands()
+    }
+
+    fn respond(&mut self, response: &CommandResponse) -> Result<(), PublishError> {
+        self.inner.respond(response)
+    }
+
+    fn poll(&mut self) {
+        self.inner.poll();
+
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.drain_and_save() {
+            log::warn!("Buffered messages not forwarded yet: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::publisher::change_filter::{Deadband, ReportPolicy};
+    use crate::publisher::memory_publisher::MemoryPublisher;
+    use crate::publisher::sensor_config::QoS;
+
+    fn config() -> SensorConfig {
+        SensorConfig {
+            topic: "plant/soil_moisture".to_string(),
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            report: ReportPolicy {
+                deadband: Deadband::Absolute(0.0),
+                heartbeat: Duration::from_secs(60),
+            },
+        }
+    }
+
+    fn publisher() -> BufferedPublisher<MemoryPublisher> {
+        let buffer_config = BufferConfig {
+            persist: false,
+            ..BufferConfig::default()
+        };
+        let sensors = vec![(Quantity::SoilMoisture, config())];
+        let inner = MemoryPublisher {
+            offline: true,
+            ..MemoryPublisher::new()
+        };
+
+        BufferedPublisher::new(inner, "memory", &buffer_config, sensors)
+    }
+
+    fn reading(value: f32) -> Reading {
+        Reading {
+            quantity: Quantity::SoilMoisture,
+            value,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn forwards_buffered_readings_in_order() {
+        let mut publisher = publisher();
+
+        assert!(publisher.publish(&config(), &reading(1.0)).is_err());
+        assert!(publisher.publish(&config(), &reading(2.0)).is_err());
+        publisher.inner.offline = false;
+        publisher.publish(&config(), &reading(3.0)).unwrap();
+
+        let values: Vec<f32> = publisher
+            .inner
+            .readings
+            .iter()
+            .map(|(_, reading)| reading.value)
+            .collect();
+        assert_eq!(values, vec![1.0, 2.0, 3.0]);
+        assert!(publisher.buffer.is_empty());
+    }
+
+    #[test]
+    fn replays_state_messages_as_a_whole() {
+        let mut publisher = publisher();
+        let state = StateMessage {
+            seq: 1,
+            timestamp: None,
+            firmware_version: "1.2.3",
+            rssi: Some(-70),
+            readings: vec![reading(1.0)],
+        };
+
+        assert!(publisher.publish_state(&state).is_err());
+        publisher.inner.offline = false;
+        publisher.poll();
+
+        assert_eq!(publisher.inner.states, vec![state]);
+        assert!(publisher.inner.readings.is_empty());
+    }
+
+    #[test]
+    fn poll_drains_the_buffer() {
+        let mut publisher = publisher();
+
+        assert!(publisher.publish(&config(), &reading(1.0)).is_err());
+        publisher.inner.offline = false;
+        publisher.poll();
+
+        assert_eq!(publisher.inner.readings.len(), 1);
+        assert!(publisher.buffer.is_empty());
+    }
+
+    #[test]
+    fn drops_refused_messages() {
+        let mut publisher = publisher();
+
+        assert!(publisher.publish(&config(), &reading(1.0)).is_err());
+        assert!(publisher.publish(&config(), &reading(2.0)).is_err());
+        publisher.inner.offline = false;
+        publisher.inner.refuse = true;
+        publisher.poll();
+
+        assert!(publisher.buffer.is_empty());
+        assert!(publisher.publish(&config(), &reading(3.0)).is_err());
+        assert!(publisher.buffer.is_empty());
+        assert_eq!(publisher.rejected, 3);
+
+        publisher.inner.refuse = false;
+        publisher.publish(&config(), &reading(4.0)).unwrap();
+        assert_eq!(publisher.inner.readings.len(), 1);
+    }
+
+    #[test]
+    fn writes_the_file_once_per_drain_pass() {
+        let path = std::env::temp_dir()
+            .join(format!("plant_doctor_drain_{}.txt", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let mut publisher = publisher();
+        publisher.file = Some(BufferFile::new(path.clone()));
+        publisher.drain_batch = 1;
+        let lines = || std::fs::read_to_string(&path).map_or(0, |file| file.lines().count());
+
+        for value in [1.0, 2.0, 3.0] {
+            assert!(publisher.publish(&config(), &reading(value)).is_err());
+        }
+        publisher.inner.offline = false;
+        publisher.poll();
+        assert_eq!((publisher.buffer.len(), lines()), (2, 3));
+
+        publisher.inner.offline = true;
+        publisher.poll();
+        assert_eq!(lines(), 2);
+
+        publisher.inner.offline = false;
+        publisher.poll();
+        publisher.poll();
+        assert!(publisher.buffer.is_empty());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::sensor::Quantity;
+
+/// Change a reading must exceed to be reported, e.g. `{ absolute = 0.5 }` or
+/// `{ relative = 0.05 }` for 5 % of the last reported value.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Deadband {
+    Absolute(f32),
+    Relative(f32),
+}
+
+impl Deadband {
+    /// Deadband of a quantity unless configured, a bit above its sensor's noise.
+    pub fn default_for(quantity: Quantity) -> Self {
+        match quantity {
+            Quantity::SoilMoisture => Deadband::Absolute(1.0),
+            Quantity::LightIntensity => Deadband::Relative(0.05),
+            Quantity::AirTemperature => Deadband::Absolute(0.2),
+            Quantity::AirHumidity => Deadband::Absolute(1.0),
+        }
+    }
+
+    fn exceeded(&self, last: f32, value: f32) -> bool {
+        let band = match *self {
+            Deadband::Absolute(band) => band,
+            Deadband::Relative(fraction) => last.abs() * fraction,
+        };
+
+        let change = (value - last).abs();
+        change.is_nan() || change > band
+    }
+}
+
+/// When the readings of a sensor are reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReportPolicy {
+    pub deadband: Deadband,
+    /// Longest time without a report, even if the value did not change.
+    pub heartbeat: Duration,
+}
+
+/// Decides which readings of one sensor are worth reporting: those leaving the
+/// deadband around the last reported value, or any once the heartbeat expired.
+pub struct ChangeFilter {
+    policy: ReportPolicy,
+    last: Option<(f32, Instant)>,
+}
+
+impl ChangeFilter {
+    pub fn new(policy: ReportPolicy) -> Self {
+        Self { policy, last: None }
+    }
+
+    pub fn should_report(&self, value: f32, now: Instant) -> bool {
+        match self.last {
+            None => true,
+            Some((last, reported_at)) => {
+                self.policy.deadband.exceeded(last, value)
+                    || now.saturating_duration_since(reported_at) >= self.policy.heartbeat
+            }
+        }
+    }
+
+    /// Marks `value` as reported, it becomes the reference of the deadband.
+    pub fn record(&mut self, value: f32, now: Instant) {
+        self.last = Some((value, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(deadband: Deadband) -> ChangeFilter {
+        ChangeFilter::new(ReportPolicy {
+            deadband,
+            heartbeat: Duration::from_secs(60),
+        })
+    }
+
+    #[test]
+    fn reports_the_first_reading() {
+        assert!(filter(Deadband::Absolute(1.0)).should_report(20.0, Instant::now()));
+    }
+
+    #[test]
+    fn absolute_deadband() {
+        let now = Instant::now();
+        let mut filter = filter(Deadband::Absolute(1.0));
+        filter.record(20.0, now);
+
+        assert!(!filter.should_report(20.9, now));
+        assert!(!filter.should_report(19.0, now));
+        assert!(filter.should_report(21.5, now));
+        assert!(filter.should_report(f32::NAN, now));
+    }
+
+    #[test]
+    fn relative_deadband() {
+        let now = Instant::now();
+        let mut filter = filter(Deadband::Relative(0.05));
+        filter.record(200.0, now);
+
+        assert!(!filter.should_report(209.0, now));
+        assert!(filter.should_report(211.0, now));
+    }
+
+    #[test]
+    fn heartbeat_reports_unchanged_values() {
+        let now = Instant::now();
+        let mut filter = filter(Deadband::Absolute(1.0));
+        filter.record(20.0, now);
+
+        assert!(!filter.should_report(20.0, now + Duration::from_secs(59)));
+        assert!(filter.should_report(20.0, now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn default_deadbands_throttle() {
+        let now = Instant::now();
+        for quantity in Quantity::ALL {
+            let mut filter = filter(Deadband::default_for(quantity));
+            filter.record(50.0, now);
+
+            assert!(!filter.should_report(50.1, now), "{:?}", quantity);
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use serde::Serialize;
+
+use crate::sensor::Quantity;
+
+const DISCOVERY_PREFIX: &str = "homeassistant";
+
+/// Identity of the device shared by all its Home Assistant entities.
+pub struct DeviceInfo {
+    pub mac: [u8; 6],
+    pub plant_name: String,
+    pub firmware_version: String,
+}
+
+impl DeviceInfo {
+    /// Node ID used in discovery topics and unique IDs, e.g. `plant_doctor_a1b2c3d4e5f6`.
+    pub fn node_id(&self) -> String {
+        let mac: String = self.mac.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("plant_doctor_{}", mac)
+    }
+
+    /// Topic with the "online" / "offline" availability of the device.
+    pub fn availability_topic(&self) -> String {
+        format!("{}/availability", self.node_id())
+    }
+
+    fn mac_string(&self) -> String {
+        self.mac
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+/// A retained message announcing one entity to Home Assistant.
+#[derive(Debug, PartialEq)]
+pub struct DiscoveryMessage {
+    pub topic: String,
+    pub payload: String,
+}
+
+#[derive(Serialize)]
+struct DevicePayload {
+    identifiers: Vec<String>,
+    connections: Vec<(&'static str, String)>,
+    name: String,
+    manufacturer: &'static str,
+    model: &'static str,
+    sw_version: String,
+}
+
+#[derive(Serialize)]
+struct EntityPayload {
+    name: &'static str,
+    unique_id: String,
+    object_id: String,
+    state_topic: String,
+    availability: Vec<AvailabilityPayload>,
+    /// `all` when the entity has its own availability besides the device's.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    availability_mode: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_template: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_class: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit_of_measurement: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_class: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entity_category: Option<&'static str>,
+    device: DevicePayload,
+}
+
+#[derive(Serialize)]
+struct AvailabilityPayload {
+    topic: String,
+}
+
+/// Home Assistant metadata of an entity.
+struct EntityKind {
+    object: &'static str,
+    name: &'static str,
+    value_template: Option<String>,
+    device_class: Option<&'static str>,
+    unit: Option<&'static str>,
+    state_class: Option<&'static str>,
+    entity_category: Option<&'static str>,
+    /// Availability of the entity itself, e.g. of a sensor that can fail.
+    availability_topic: Option<String>,
+}
+
+const VALUE_TEMPLATE: &str = "{{ value_json.value }}";
+
+/// Home Assistant name and device class of a sensor.
+pub fn sensor_metadata(quantity: &Quantity) -> (&'static str, &'static str) {
+    match quantity {
+        Quantity::SoilMoisture => ("Soil moisture", "moisture"),
+        Quantity::LightIntensity => ("Light intensity", "illuminance"),
+        Quantity::AirTemperature => ("Air temperature", "temperature"),
+        Quantity::AirHumidity => ("Air humidity", "humidity"),
+    }
+}
+
+fn sensor_kind(
+    quantity: &Quantity,
+    value_template: String,
+    availability_topic: &str,
+) -> EntityKind {
+    let (name, device_class) = sensor_metadata(quantity);
+
+    EntityKind {
+        object: quantity.key(),
+        name,
+        value_template: Some(value_template),
+        device_class: Some(device_class),
+        unit: Some(quantity.unit()),
+        state_class: Some("measurement"),
+        entity_category: None,
+        availability_topic: Some(availability_topic.to_string()),
+    }
+}
+
+/// Builds the discovery message for a sensor publishing `{"value": x}` to `state_topic`.
+pub fn sensor_discovery(
+    device: &DeviceInfo,
+    quantity: &Quantity,
+    state_topic: &str,
+    availability_topic: &str,
+) -> DiscoveryMessage {
+    let kind = sensor_kind(quantity, VALUE_TEMPLATE.to_string(), availability_topic);
+    entity_discovery(device, kind, state_topic)
+}
+
+/// Builds the discovery message for a sensor whose readings are part of the
+/// batched state message on `state_topic`.
+pub fn batched_sensor_discovery(
+    device: &DeviceInfo,
+    quantity: &Quantity,
+    state_topic: &str,
+    availability_topic: &str,
+) -> DiscoveryMessage {
+    let template = format!("{{{{ value_json.readings.{}.value }}}}", quantity.key());
+    let kind = sensor_kind(quantity, template, availability_topic);
+    entity_discovery(device, kind, state_topic)
+}
+
+/// Builds the discovery message for the Wi-Fi signal strength in the batched state message.
+pub fn rssi_discovery(device: &DeviceInfo, state_topic: &str) -> DiscoveryMessage {
+    let kind = EntityKind {
+        object: "rssi",
+        name: "Wi-Fi signal",
+        value_template: Some("{{ value_json.rssi }}".to_string()),
+        device_class: Some("signal_strength"),
+        unit: Some("dBm"),
+        state_class: Some("measurement"),
+        entity_category: Some("diagnostic"),
+        availability_topic: None,
+    };
+
+    entity_discovery(device, kind, state_topic)
+}
+
+/// Builds the discovery message for the plant name published as plain text.
+pub fn plant_name_discovery(device: &DeviceInfo, state_topic: &str) -> DiscoveryMessage {
+    let kind = EntityKind {
+        object: "plant_name",
+        name: "Plant name",
+        value_template: None,
+        device_class: None,
+        unit: None,
+        state_class: None,
+        entity_category: Some("diagnostic"),
+        availability_topic: None,
+    };
+
+    entity_discovery(device, kind, state_topic)
+}
+
+fn entity_discovery(device: &DeviceInfo, kind: EntityKind, state_topic: &str) -> DiscoveryMessage {
+    let node_id = device.node_id();
+    let unique_id = format!("{}_{}", node_id, kind.object);
+
+    let mut availability = vec![AvailabilityPayload {
+        topic: device.availability_topic(),
+    }];
+    if let Some(topic) = kind.availability_topic {
+        availability.push(AvailabilityPayload { topic });
+    }
+    let availability_mode = (availability.len() > 1).then_some("all");
+
+    let payload = EntityPayload {
+        name: kind.name,
+        object_id: unique_id.clone(),
+        unique_id,
+        state_topic: state_topic.to_string(),
+        availability,
+        availability_mode,
+        value_template: kind.value_template,
+        device_class: kind.device_class,
+        unit_of_measurement: kind.unit,
+        state_class: kind.state_class,
+        entity_category: kind.entity_category,
+        device: device_payload(device),
+    };
+
+    DiscoveryMessage {
+        topic: format!(
+            "{}/sensor/{}/{}/config",
+            DISCOVERY_PREFIX, node_id, kind.object
+        ),
+        payload: serde_json::to_string(&payload).expect("Discovery payload is serializable"),
+    }
+}
+
+fn device_payload(device: &DeviceInfo) -> DevicePayload {
+    DevicePayload {
+        identifiers: vec![device.node_id()],
+        connections: vec![("mac", device.mac_string())],
+        name: device.plant_name.clone(),
+        manufacturer: "Plant doctor",
+        model: "Plant doctor",
+        sw_version: device.firmware_version.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE_JSON: &str = concat!(
+        r#""device":{"identifiers":["plant_doctor_a1b2c3d4e5f6"],"#,
+        r#""connections":[["mac","a1:b2:c3:d4:e5:f6"]],"name":"Monstera","#,
+        r#""manufacturer":"Plant doctor","model":"Plant doctor","sw_version":"1.2.3"}"#,
+    );
+
+    fn device() -> DeviceInfo {
+        DeviceInfo {
+            mac: [0xa1, 0xb2, 0xc3, 0xd4, 0xe5, 0xf6],
+            plant_name: "Monstera".to_string(),
+            firmware_version: "1.2.3".to_string(),
+        }
+    }
+
+    fn expected(topic: &str, entity_json: &str) -> DiscoveryMessage {
+        DiscoveryMessage {
+            topic: topic.to_string(),
+            payload: format!("{{{},{}}}", entity_json, DEVICE_JSON),
+        }
+    }
+
+    #[test]
+    fn sensor_discovery_snapshot() {
+        let message = sensor_discovery(
+            &device(),
+            &Quantity::SoilMoisture,
+            "plants/monstera/soil_moisture",
+            "plants/monstera/soil_moisture/availability",
+        );
+
+        assert_eq!(
+            message,
+            expected(
+                "homeassistant/sensor/plant_doctor_a1b2c3d4e5f6/soil_moisture/config",
+                concat!(
+                    r#""name":"Soil moisture","#,
+                    r#""unique_id":"plant_doctor_a1b2c3d4e5f6_soil_moisture","#,
+                    r#""object_id":"plant_doctor_a1b2c3d4e5f6_soil_moisture","#,
+                    r#""state_topic":"plants/monstera/soil_moisture","#,
+                    r#""availability":[{"topic":"plant_doctor_a1b2c3d4e5f6/availability"},"#,
+                    r#"{"topic":"plants/monstera/soil_moisture/availability"}],"#,
+                    r#""availability_mode":"all","value_template":"{{ value_json.value }}","#,
+                    r#""device_class":"moisture","unit_of_measurement":"%","#,
+                    r#""state_class":"measurement""#,
+                ),
+            )
+        );
+    }
+
+    #[test]
+    fn batched_sensor_discovery_snapshot() {
+        let message = batched_sensor_discovery(
+            &device(),
+            &Quantity::AirTemperature,
+            "plants/monstera/state",
+            "plants/monstera/air_temperature/availability",
+        );
+
+        assert_eq!(
+            message,
+            expected(
+                "homeassistant/sensor/plant_doctor_a1b2c3d4e5f6/air_temperature/config",
+                concat!(
+                    r#""name":"Air temperature","#,
+                    r#""unique_id":"plant_doctor_a1b2c3d4e5f6_air_temperature","#,
+                    r#""object_id":"plant_doctor_a1b2c3d4e5f6_air_temperature","#,
+                    r#""state_topic":"plants/monstera/state","#,
+                    r#""availability":[{"topic":"plant_doctor_a1b2c3d4e5f6/availability"},"#,
+                    r#"{"topic":"plants/monstera/air_temperature/availability"}],"#,
+                    r#""availability_mode":"all","#,
+                    r#""value_template":"{{ value_json.readings.air_temperature.value }}","#,
+                    r#""device_class":"temperature","unit_of_measurement":"°C","#,
+                    r#""state_class":"measurement""#,
+                ),
+            )
+        );
+    }
+
+    #[test]
+    fn rssi_discovery_snapshot() {
+        let message = rssi_discovery(&device(), "plants/monstera/state");
+
+        assert_eq!(
+            message,
+            expected(
+                "homeassistant/sensor/plant_doctor_a1b2c3d4e5f6/rssi/config",
+                concat!(
+                    r#""name":"Wi-Fi signal","#,
+                    r#""unique_id":"plant_doctor_a1b2c3d4e5f6_rssi","#,
+                    r#""object_id":"plant_doctor_a1b2c3d4e5f6_rssi","#,
+                    r#""state_topic":"plants/monstera/state","#,
+                    r#""availability":[{"topic":"plant_doctor_a1b2c3d4e5f6/availability"}],"#,
+                    r#""value_template":"{{ value_json.rssi }}","#,
+                    r#""device_class":"signal_strength","unit_of_measurement":"dBm","#,
+                    r#""state_class":"measurement","entity_category":"diagnostic""#,
+                ),
+            )
+        );
+    }
+
+    #[test]
+    fn plant_name_discovery_snapshot() {
+        let message = plant_name_discovery(&device(), "plants/monstera/plant_name");
+
+        assert_eq!(
+            message,
+            expected(
+                "homeassistant/sensor/plant_doctor_a1b2c3d4e5f6/plant_name/config",
+                concat!(
+                    r#""name":"Plant name","#,
+                    r#""unique_id":"plant_doctor_a1b2c3d4e5f6_plant_name","#,
+                    r#""object_id":"plant_doctor_a1b2c3d4e5f6_plant_name","#,
+                    r#""state_topic":"plants/monstera/plant_name","#,
+                    r#""availability":[{"topic":"plant_doctor_a1b2c3d4e5f6/availability"}],"#,
+                    r#""entity_category":"diagnostic""#,
+                ),
+            )
+        );
+    }
+
+    #[test]
+    fn every_quantity_has_its_own_topic() {
+        let topics: std::collections::BTreeSet<String> = Quantity::ALL
+            .iter()
+            .map(|quantity| sensor_discovery(&device(), quantity, "state", "availability").topic)
+            .collect();
+
+        assert_eq!(topics.len(), Quantity::ALL.len());
+    }
+}
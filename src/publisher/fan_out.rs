@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::health::status::ConnectivityStatus;
+use crate::metrics::Metrics;
+use crate::sensor::{Quantity, Reading};
+
+/// Sends everything to each backend in turn. Every backend keeps its own
+/// buffer and retry state, a failing one is logged and counted in the metrics
+/// without affecting the others.
+pub struct FanOutPublisher {
+    backends: Vec<Backend>,
+    metrics: Metrics,
+}
+
+struct Backend {
+    name: &'static str,
+    publisher: Box<dyn Publisher>,
+    /// Availability this backend accepted per quantity, so a retry only goes
+    /// to the backends that failed.
+    availability: BTreeMap<Quantity, bool>,
+}
+
+impl FanOutPublisher {
+    pub fn new(backends: Vec<(&'static str, Box<dyn Publisher>)>, metrics: Metrics) -> Self {
+        let backends = backends
+            .into_iter()
+            .map(|(name, publisher)| Backend {
+                name,
+                publisher,
+                availability: BTreeMap::new(),
+            })
+            .collect();
+
+        Self { backends, metrics }
+    }
+
+    /// Calls `f` for every backend. Fails if any backend failed, with the
+    /// error of the last one.
+    fn each(
+        &mut self,
+        what: &str,
+        mut f: impl FnMut(&mut Backend) -> Option<Result<(), PublishError>>,
+    ) -> Result<(), PublishError> {
+        let mut last_error = None;
+
+        for backend in &mut self.backends {
+            // `None` means there was nothing to send to this backend
+            let Some(result) = f(backend) else {
+                continue;
+            };
+            self.metrics.record_publish(backend.name, result.is_ok());
+
+            if let Err(e) = result {
+                log::warn!("Error publishing {} to {}: {}", what, backend.name, e);
+                last_error = Some(e);
+            }
+        }
+
+        match last_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Publisher for FanOutPublisher {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        self.each(reading.quantity.key(), |backend| {
+            Some(backend.publisher.publish(config, reading))
+        })
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        self.each("state", |backend| {
+            Some(backend.publisher.publish_state(state))
+        })
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        self.each("connectivity", |backend| {
+            Some(
+                backend
+                    .publisher
+                    .publish_connectivity(status, consecutive_failures),
+            )
+        })
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        self.each("plant name", |backend| {
+            Some(backend.publisher.publish_plant_name(name))
+        })
+    }
+
+    fn publish_availability(
+        &mut self,
+        config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        self.each("availability", |backend| {
+            if backend.availability.get(&quantity) == Some(&available) {
+                return None;
+            }
+            let result = backend
+                .publisher
+                .publish_availability(config, quantity, available);
+            if result.is_ok() {
+                backend.availability.insert(quantity, available);
+            }
+            Some(result)
+        })
+    }
+
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        self.backends
+            .iter_mut()
+            .flat_map(|backend| backend.publisher.commands())
+            .collect()
+    }
+
+    /// Backends that do not receive commands ignore the response.
+    fn respond(&mut self, response: &CommandResponse) -> Result<(), PublishError> {
+        self.each("command response", |backend| {
+            Some(backend.publisher.respond(response))
+        })
+    }
+
+    fn poll(&mut self) {
+        for backend in &mut self.backends {
+            backend.publisher.poll();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::publisher::change_filter::{Deadband, ReportPolicy};
+    use crate::publisher::memory_publisher::MemoryPublisher;
+    use crate::publisher::sensor_config::QoS;
+
+    /// Lets the test look at a backend after handing it to the fan-out.
+    struct Shared(Rc<RefCell<MemoryPublisher>>);
+
+    impl Publisher for Shared {
+        fn publish(
+            &mut self,
+            config: &SensorConfig,
+            reading: &Reading,
+        ) -> Result<(), PublishError> {
+            self.0.borrow_mut().publish(config, reading)
+        }
+
+        fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+            self.0.borrow_mut().publish_state(state)
+        }
+
+        fn publish_connectivity(
+            &mut self,
+            status: &ConnectivityStatus,
+            consecutive_failures: u32,
+        ) -> Result<(), PublishError> {
+            self.0
+                .borrow_mut()
+                .publish_connectivity(status, consecutive_failures)
+        }
+
+        fn publish_availability(
+            &mut self,
+            config: &SensorConfig,
+            quantity: Quantity,
+            available: bool,
+        ) -> Result<(), PublishError> {
+            self.0
+                .borrow_mut()
+                .publish_availability(config, quantity, available)
+        }
+    }
+
+    fn fan_out() -> (
+        FanOutPublisher,
+        Rc<RefCell<MemoryPublisher>>,
+        Rc<RefCell<MemoryPublisher>>,
+    ) {
+        let mqtt = Rc::new(RefCell::new(MemoryPublisher::new()));
+        let influxdb = Rc::new(RefCell::new(MemoryPublisher::new()));
+        let backends: Vec<(&'static str, Box<dyn Publisher>)> = vec![
+            ("mqtt", Box::new(Shared(mqtt.clone()))),
+            ("influxdb", Box::new(Shared(influxdb.clone()))),
+        ];
+        let metrics = Metrics::new(Quantity::ALL, [], ["mqtt", "influxdb"]);
+
+        (FanOutPublisher::new(backends, metrics), mqtt, influxdb)
+    }
+
+    fn config() -> SensorConfig {
+        SensorConfig {
+            topic: "plant/soil_moisture".to_string(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+            report: ReportPolicy {
+                deadband: Deadband::Absolute(1.0),
+                heartbeat: Duration::from_secs(60),
+            },
+        }
+    }
+
+    #[test]
+    fn fails_if_any_backend_fails() {
+        let (mut publisher, mqtt, influxdb) = fan_out();
+        mqtt.borrow_mut().offline = true;
+
+        let reading = Reading::now(Quantity::SoilMoisture, 40.0);
+        assert!(publisher.publish(&config(), &reading).is_err());
+
+        assert_eq!(influxdb.borrow().readings.len(), 1);
+        let snapshot = publisher.metrics.snapshot();
+        assert_eq!(snapshot.backends["mqtt"].failures, 1);
+        assert_eq!(snapshot.backends["influxdb"].successes, 1);
+    }
+
+    #[test]
+    fn retries_availability_only_on_failed_backends() {
+        let (mut publisher, mqtt, influxdb) = fan_out();
+        mqtt.borrow_mut().offline = true;
+
+        assert!(publisher
+            .publish_availability(&config(), Quantity::SoilMoisture, true)
+            .is_err());
+        mqtt.borrow_mut().offline = false;
+        assert!(publisher
+            .publish_availability(&config(), Quantity::SoilMoisture, true)
+            .is_ok());
+
+        let expected = vec![(Quantity::SoilMoisture, true)];
+        assert_eq!(mqtt.borrow().availability, expected);
+        assert_eq!(influxdb.borrow().availability, expected);
+    }
+}
@@ -0,0 +1,311 @@
+use std::time::Instant;
+
+use serde_json::{json, Value};
+
+use super::discovery::{sensor_metadata, DeviceInfo};
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::health::status::ConnectivityStatus;
+use crate::http_client::{HttpClient, RetryBackoff};
+use crate::sensor::{Quantity, Reading};
+
+const UNAVAILABLE: &str = "unavailable";
+
+/// Sets entity states through the Home Assistant REST API (`POST /api/states/<entity_id>`),
+/// for setups without an MQTT broker. Entities are named `sensor.<node_id>_<sensor>`.
+pub struct HomeAssistantApiPublisher<C: HttpClient> {
+    client: C,
+    api_url: String,
+    authorization: String,
+    device: DeviceInfo,
+    retry: RetryBackoff,
+}
+
+impl<C: HttpClient> HomeAssistantApiPublisher<C> {
+    /// `api_url` is the base URL of Home Assistant, e.g. `http://homeassistant.local:8123`,
+    /// `token` a long-lived access token.
+    pub fn new(client: C, api_url: &str, token: &str, device: DeviceInfo) -> Self {
+        Self {
+            client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            authorization: format!("Bearer {}", token),
+            device,
+            retry: RetryBackoff::new(),
+        }
+    }
+
+    fn set_state(
+        &mut self,
+        object: &str,
+        state: &str,
+        attributes: Value,
+    ) -> Result<(), PublishError> {
+        // Do not block the sensor loop on an unreachable server
+        let now = Instant::now();
+        if !self.retry.is_due(now) {
+            return Err(PublishError::NotConnected);
+        }
+
+        let url = format!(
+            "{}/api/states/sensor.{}_{}",
+            self.api_url,
+            self.device.node_id(),
+            object
+        );
+        let body = json!({ "state": state, "attributes": attributes }).to_string();
+        let headers = [
+            ("Authorization", self.authorization.as_str()),
+            ("Content-Type", "application/json"),
+        ];
+
+        match self.client.post(&url, &headers, body.as_bytes()) {
+            Ok(_) => {
+                self.retry.succeeded();
+                Ok(())
+            }
+            Err(e) => {
+                if e.is_retryable() {
+                    let delay = self.retry.failed(now);
+                    log::warn!("Home Assistant unreachable, retrying in {:?}", delay);
+                }
+                Err(PublishError::Http(e))
+            }
+        }
+    }
+
+    fn set_reading(&mut self, reading: &Reading) -> Result<(), PublishError> {
+        let state = if reading.value.is_finite() {
+            reading.value.to_string()
+        } else {
+            UNAVAILABLE.to_string()
+        };
+
+        let mut attributes = self.sensor_attributes(reading.quantity);
+        if let Some(timestamp) = reading.timestamp {
+            attributes["measured_at"] = json!(timestamp.to_iso8601());
+        }
+
+        self.set_state(reading.quantity.key(), &state, attributes)
+    }
+
+    fn sensor_attributes(&self, quantity: Quantity) -> Value {
+        let (name, device_class) = sensor_metadata(&quantity);
+
+        json!({
+            "friendly_name": self.friendly_name(name),
+            "unit_of_measurement": quantity.unit(),
+            "device_class": device_class,
+            "state_class": "measurement",
+        })
+    }
+
+    fn friendly_name(&self, name: &str) -> String {
+        format!("{} {}", self.device.plant_name, name.to_lowercase())
+    }
+}
+
+impl<C: HttpClient> Publisher for HomeAssistantApiPublisher<C> {
+    fn publish(&mut self, _config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        self.set_reading(reading)
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        for reading in &state.readings {
+            self.set_reading(reading)?;
+        }
+
+        if let Some(rssi) = state.rssi {
+            let attributes = json!({
+                "friendly_name": self.friendly_name("Wi-Fi signal"),
+                "unit_of_measurement": "dBm",
+                "device_class": "signal_strength",
+                "state_class": "measurement",
+            });
+            self.set_state("rssi", &rssi.to_string(), attributes)?;
+        }
+
+        Ok(())
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        let attributes = json!({
+            "friendly_name": self.friendly_name("Connectivity"),
+            "gateway": status.gateway.to_string(),
+            "broker": status.broker.to_string(),
+            "dns": status.dns.to_string(),
+            "consecutive_failures": consecutive_failures,
+        });
+
+        self.set_state("connectivity", status.problem().unwrap_or("ok"), attributes)
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        self.device.plant_name = name.to_string();
+
+        let attributes = json!({
+            "friendly_name": self.friendly_name("Plant name"),
+            "icon": "mdi:flower",
+        });
+        self.set_state("plant_name", name, attributes)
+    }
+
+    /// Only the failure is sent, the next reading replaces the unavailable state.
+    fn publish_availability(
+        &mut self,
+        _config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        if available {
+            return Ok(());
+        }
+
+        let attributes = self.sensor_attributes(quantity);
+        self.set_state(quantity.key(), UNAVAILABLE, attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::http_client::{HttpError, StubHttpClient};
+    use crate::publisher::change_filter::{Deadband, ReportPolicy};
+    use crate::publisher::sensor_config::QoS;
+
+    fn publisher() -> HomeAssistantApiPublisher<StubHttpClient> {
+        let device = DeviceInfo {
+            mac: [0xa1, 0xb2, 0xc3, 0xd4, 0xe5, 0xf6],
+            plant_name: "Monstera".to_string(),
+            firmware_version: "1.2.3".to_string(),
+        };
+        HomeAssistantApiPublisher::new(
+            StubHttpClient::new(),
+            "http://ha.local:8123/",
+            "secret",
+            device,
+        )
+    }
+
+    fn config() -> SensorConfig {
+        SensorConfig {
+            topic: "plant/soil_moisture".to_string(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+            report: ReportPolicy {
+                deadband: Deadband::Absolute(1.0),
+                heartbeat: Duration::from_secs(60),
+            },
+        }
+    }
+
+    fn reading() -> Reading {
+        Reading {
+            quantity: Quantity::SoilMoisture,
+            value: 41.5,
+            timestamp: None,
+        }
+    }
+
+    fn body(publisher: &HomeAssistantApiPublisher<StubHttpClient>, index: usize) -> Value {
+        serde_json::from_str(&publisher.client.requests()[index].body).unwrap()
+    }
+
+    #[test]
+    fn posts_readings_as_entity_states() {
+        let mut publisher = publisher();
+
+        publisher.publish(&config(), &reading()).unwrap();
+
+        let request = &publisher.client.requests()[0];
+        assert_eq!(
+            request.url,
+            "http://ha.local:8123/api/states/sensor.plant_doctor_a1b2c3d4e5f6_soil_moisture"
+        );
+        assert_eq!(request.header("Authorization"), Some("Bearer secret"));
+        let body = body(&publisher, 0);
+        assert_eq!(body["state"], "41.5");
+        assert_eq!(
+            body["attributes"]["friendly_name"],
+            "Monstera soil moisture"
+        );
+        assert_eq!(body["attributes"]["unit_of_measurement"], "%");
+    }
+
+    #[test]
+    fn backs_off_after_a_retryable_failure() {
+        let mut publisher = publisher();
+        publisher
+            .client
+            .respond_with(Err(HttpError::Transport("timed out".to_string())));
+
+        assert!(matches!(
+            publisher.publish(&config(), &reading()),
+            Err(PublishError::Http(_))
+        ));
+        assert!(matches!(
+            publisher.publish(&config(), &reading()),
+            Err(PublishError::NotConnected)
+        ));
+        assert_eq!(publisher.client.requests().len(), 1);
+    }
+
+    #[test]
+    fn rejected_requests_do_not_back_off() {
+        let mut publisher = publisher();
+        publisher.client.respond_with(Err(HttpError::Status(400)));
+
+        assert!(publisher.publish(&config(), &reading()).is_err());
+        publisher.publish(&config(), &reading()).unwrap();
+        assert_eq!(publisher.client.requests().len(), 2);
+    }
+
+    #[test]
+    fn only_unavailability_is_posted() {
+        let mut publisher = publisher();
+
+        publisher
+            .publish_availability(&config(), Quantity::SoilMoisture, true)
+            .unwrap();
+        publisher
+            .publish_availability(&config(), Quantity::SoilMoisture, false)
+            .unwrap();
+
+        assert_eq!(publisher.client.requests().len(), 1);
+        assert_eq!(body(&publisher, 0)["state"], UNAVAILABLE);
+    }
+
+    #[test]
+    fn state_posts_every_reading_and_the_signal() {
+        let mut publisher = publisher();
+        let state = StateMessage {
+            seq: 1,
+            timestamp: None,
+            firmware_version: "1.2.3",
+            rssi: Some(-61),
+            readings: vec![reading()],
+        };
+
+        publisher.publish_state(&state).unwrap();
+
+        let requests = publisher.client.requests();
+        let urls: Vec<&str> = requests
+            .iter()
+            .map(|request| request.url.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "sensor.plant_doctor_a1b2c3d4e5f6_soil_moisture",
+                "sensor.plant_doctor_a1b2c3d4e5f6_rssi"
+            ]
+        );
+        assert_eq!(body(&publisher, 1)["state"], "-61");
+    }
+}
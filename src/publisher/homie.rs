@@ -0,0 +1,328 @@
+use std::time::Duration;
+
+use super::discovery::sensor_metadata;
+use crate::sensor::Quantity;
+
+/// Root of all Homie devices.
+const HOMIE_PREFIX: &str = "homie";
+const HOMIE_VERSION: &str = "4.0.0";
+/// The device has a single node with the sensors and settings of the plant.
+const NODE_ID: &str = "plant";
+
+/// Value of the `$state` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceState {
+    Init,
+    Ready,
+    Lost,
+    Alert,
+}
+
+impl DeviceState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceState::Init => "init",
+            DeviceState::Ready => "ready",
+            DeviceState::Lost => "lost",
+            DeviceState::Alert => "alert",
+        }
+    }
+
+    /// `alert` while the connectivity checks fail or a sensor cannot be read.
+    pub fn reported(connectivity_healthy: bool, sensors_available: bool) -> Self {
+        if connectivity_healthy && sensors_available {
+            DeviceState::Ready
+        } else {
+            DeviceState::Alert
+        }
+    }
+}
+
+/// A property of the plant node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Property {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub datatype: &'static str,
+    pub unit: Option<&'static str>,
+    pub format: Option<&'static str>,
+    /// Command carried out for a message on `<property>/set`, `None` if the
+    /// property is read-only.
+    pub command: Option<&'static str>,
+}
+
+const PLANT_NAME: Property = Property {
+    id: "name",
+    name: "Plant name",
+    datatype: "string",
+    unit: None,
+    format: None,
+    command: Some("plant_name"),
+};
+
+const SAMPLING_INTERVAL: Property = Property {
+    id: "sampling-interval",
+    name: "Sampling interval",
+    datatype: "integer",
+    unit: Some("ms"),
+    format: Some("100:86400000"),
+    command: Some("sampling_interval"),
+};
+
+const SETTABLE_PROPERTIES: [Property; 2] = [PLANT_NAME, SAMPLING_INTERVAL];
+
+/// Topic of the device, e.g. `homie/plant-doctor-a1b2c3`.
+pub fn device_topic(hostname: &str) -> String {
+    format!("{}/{}", HOMIE_PREFIX, hostname)
+}
+
+pub fn state_topic(device_topic: &str) -> String {
+    format!("{}/$state", device_topic)
+}
+
+pub fn property_topic(device_topic: &str, property: &Property) -> String {
+    format!("{}/{}/{}", device_topic, NODE_ID, property.id)
+}
+
+/// Subscription matching the `/set` topics of all properties.
+pub fn set_filter(device_topic: &str) -> String {
+    format!("{}/{}/+/set", device_topic, NODE_ID)
+}
+
+/// Command to carry out for a message on a `/set` topic, `None` for other
+/// topics and read-only properties.
+pub fn set_command(device_topic: &str, topic: &str) -> Option<&'static str> {
+    let id = topic
+        .strip_prefix(device_topic)?
+        .strip_prefix('/')?
+        .strip_prefix(NODE_ID)?
+        .strip_prefix('/')?
+        .strip_suffix("/set")?;
+
+    SETTABLE_PROPERTIES
+        .iter()
+        .find(|property| property.id == id)
+        .and_then(|property| property.command)
+}
+
+/// Settable property changed by the command, whose new value is published
+/// once the command succeeded.
+pub fn command_property(command: &str) -> Option<&'static Property> {
+    SETTABLE_PROPERTIES
+        .iter()
+        .find(|property| property.command == Some(command))
+}
+
+pub fn sensor_property(quantity: &Quantity) -> Property {
+    let (name, _) = sensor_metadata(quantity);
+    let id = match quantity {
+        Quantity::SoilMoisture => "soil-moisture",
+        Quantity::LightIntensity => "light-intensity",
+        Quantity::AirTemperature => "air-temperature",
+        Quantity::AirHumidity => "air-humidity",
+    };
+
+    Property {
+        id,
+        name,
+        datatype: "float",
+        unit: Some(quantity.unit()),
+        format: match quantity {
+            Quantity::SoilMoisture | Quantity::AirHumidity => Some("0:100"),
+            _ => None,
+        },
+        command: None,
+    }
+}
+
+/// Everything announced about the device when it connects.
+pub struct Description<'a> {
+    pub device_topic: &'a str,
+    pub device_name: &'a str,
+    pub plant_name: &'a str,
+    pub sampling_interval: Duration,
+    pub quantities: &'a [Quantity],
+}
+
+/// Retained messages with the device, node and property attributes and the
+/// current values of the settable properties, in the order they must be published.
+pub fn description_messages(description: &Description) -> Vec<(String, String)> {
+    let device = description.device_topic;
+    let node = format!("{}/{}", device, NODE_ID);
+
+    let properties: Vec<Property> = description
+        .quantities
+        .iter()
+        .map(sensor_property)
+        .chain(SETTABLE_PROPERTIES)
+        .collect();
+    let property_ids: Vec<&str> = properties.iter().map(|property| property.id).collect();
+
+    let mut messages = vec![
+        (state_topic(device), DeviceState::Init.as_str().to_string()),
+        (format!("{}/$homie", device), HOMIE_VERSION.to_string()),
+        (
+            format!("{}/$name", device),
+            description.device_name.to_string(),
+        ),
+        (format!("{}/$nodes", device), NODE_ID.to_string()),
+        (format!("{}/$extensions", device), String::new()),
+        (
+            format!("{}/$name", node),
+            description.plant_name.to_string(),
+        ),
+        (format!("{}/$type", node), "Plant".to_string()),
+        (format!("{}/$properties", node), property_ids.join(",")),
+    ];
+
+    for property in &properties {
+        let topic = property_topic(device, property);
+        messages.push((format!("{}/$name", topic), property.name.to_string()));
+        messages.push((
+            format!("{}/$datatype", topic),
+            property.datatype.to_string(),
+        ));
+        if let Some(unit) = property.unit {
+            messages.push((format!("{}/$unit", topic), unit.to_string()));
+        }
+        if let Some(format) = property.format {
+            messages.push((format!("{}/$format", topic), format.to_string()));
+        }
+        if property.command.is_some() {
+            messages.push((format!("{}/$settable", topic), "true".to_string()));
+        }
+    }
+
+    messages.push((
+        property_topic(device, &PLANT_NAME),
+        description.plant_name.to_string(),
+    ));
+    messages.push((
+        property_topic(device, &SAMPLING_INTERVAL),
+        description.sampling_interval.as_millis().to_string(),
+    ));
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alert_while_anything_fails() {
+        assert_eq!(DeviceState::reported(true, true), DeviceState::Ready);
+        assert_eq!(DeviceState::reported(false, true), DeviceState::Alert);
+        assert_eq!(DeviceState::reported(true, false), DeviceState::Alert);
+    }
+
+    const DEVICE: &str = "homie/plant-doctor-a1b2c3";
+
+    #[test]
+    fn description_snapshot() {
+        let messages = description_messages(&Description {
+            device_topic: DEVICE,
+            device_name: "Plant doctor",
+            plant_name: "Monstera",
+            sampling_interval: Duration::from_secs(60),
+            quantities: &[Quantity::SoilMoisture, Quantity::LightIntensity],
+        });
+        let messages: Vec<(&str, &str)> = messages
+            .iter()
+            .map(|(topic, payload)| {
+                let topic = topic.strip_prefix(DEVICE).unwrap();
+                (topic, payload.as_str())
+            })
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                ("/$state", "init"),
+                ("/$homie", "4.0.0"),
+                ("/$name", "Plant doctor"),
+                ("/$nodes", "plant"),
+                ("/$extensions", ""),
+                ("/plant/$name", "Monstera"),
+                ("/plant/$type", "Plant"),
+                (
+                    "/plant/$properties",
+                    "soil-moisture,light-intensity,name,sampling-interval"
+                ),
+                ("/plant/soil-moisture/$name", "Soil moisture"),
+                ("/plant/soil-moisture/$datatype", "float"),
+                ("/plant/soil-moisture/$unit", "%"),
+                ("/plant/soil-moisture/$format", "0:100"),
+                ("/plant/light-intensity/$name", "Light intensity"),
+                ("/plant/light-intensity/$datatype", "float"),
+                ("/plant/light-intensity/$unit", "lx"),
+                ("/plant/name/$name", "Plant name"),
+                ("/plant/name/$datatype", "string"),
+                ("/plant/name/$settable", "true"),
+                ("/plant/sampling-interval/$name", "Sampling interval"),
+                ("/plant/sampling-interval/$datatype", "integer"),
+                ("/plant/sampling-interval/$unit", "ms"),
+                ("/plant/sampling-interval/$format", "100:86400000"),
+                ("/plant/sampling-interval/$settable", "true"),
+                ("/plant/name", "Monstera"),
+                ("/plant/sampling-interval", "60000"),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_topics_of_settable_properties() {
+        assert_eq!(
+            set_command(DEVICE, "homie/plant-doctor-a1b2c3/plant/name/set"),
+            Some("plant_name")
+        );
+        assert_eq!(
+            set_command(
+                DEVICE,
+                "homie/plant-doctor-a1b2c3/plant/sampling-interval/set"
+            ),
+            Some("sampling_interval")
+        );
+        assert_eq!(
+            set_command(DEVICE, "homie/plant-doctor-a1b2c3/plant/soil-moisture/set"),
+            None
+        );
+        assert_eq!(
+            set_command(DEVICE, "homie/plant-doctor-d4e5f6/plant/name/set"),
+            None
+        );
+        assert_eq!(
+            set_command(DEVICE, "homie/plant-doctor-a1b2c3/plant/name"),
+            None
+        );
+        assert_eq!(set_filter(DEVICE), "homie/plant-doctor-a1b2c3/plant/+/set");
+        assert_eq!(
+            command_property("sampling_interval").map(|property| property.id),
+            Some("sampling-interval")
+        );
+    }
+
+    #[test]
+    fn sensor_properties() {
+        assert_eq!(
+            sensor_property(&Quantity::AirHumidity),
+            Property {
+                id: "air-humidity",
+                name: "Air humidity",
+                datatype: "float",
+                unit: Some("%"),
+                format: Some("0:100"),
+                command: None,
+            }
+        );
+        let temperature = sensor_property(&Quantity::AirTemperature);
+        assert_eq!(
+            (temperature.id, temperature.unit, temperature.format),
+            ("air-temperature", Some("°C"), None)
+        );
+        assert_eq!(
+            property_topic(DEVICE, &temperature),
+            "homie/plant-doctor-a1b2c3/plant/air-temperature"
+        );
+    }
+}
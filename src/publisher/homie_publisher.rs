@@ -0,0 +1,159 @@
+use std::collections::BTreeSet;
+
+use esp_idf_svc::mqtt::client::QoS;
+
+use super::homie::{self, DeviceState};
+use super::mqtt_connection::MqttConnection;
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::health::status::ConnectivityStatus;
+use crate::sensor::{Quantity, Reading};
+
+/// Publishes the readings as retained values of the plant node's properties,
+/// following the Homie 4 convention. The sensor topics are not used.
+pub struct HomiePublisher {
+    connection: MqttConnection,
+    device_topic: String,
+    /// New values of properties set over MQTT, published once the command succeeded.
+    pending_values: Vec<(&'static str, String)>,
+    connectivity_healthy: bool,
+    /// Quantities whose sensor failed, the device is in `alert` while any are.
+    unavailable: BTreeSet<Quantity>,
+}
+
+impl HomiePublisher {
+    pub fn new(connection: MqttConnection, device_topic: String) -> Self {
+        Self {
+            connection,
+            device_topic,
+            pending_values: Vec::new(),
+            connectivity_healthy: true,
+            unavailable: BTreeSet::new(),
+        }
+    }
+
+    fn send(&mut self, topic: &str, payload: &str) -> Result<(), PublishError> {
+        if !self.connection.is_connected() {
+            return Err(PublishError::NotConnected);
+        }
+
+        let id = self
+            .connection
+            .client()
+            .publish(topic, QoS::AtLeastOnce, true, payload.as_bytes())
+            .map_err(PublishError::Mqtt)?;
+        log::info!("Published message with id {}", id);
+
+        Ok(())
+    }
+
+    fn send_state(&mut self) -> Result<(), PublishError> {
+        let state = DeviceState::reported(self.connectivity_healthy, self.unavailable.is_empty());
+        self.connection.set_online(state.as_str());
+        let topic = homie::state_topic(&self.device_topic);
+        self.send(&topic, state.as_str())
+    }
+
+    fn publish_reading(&mut self, reading: &Reading) -> Result<(), PublishError> {
+        if !reading.value.is_finite() {
+            log::warn!("Not publishing invalid reading {:?}", reading);
+            return Ok(());
+        }
+
+        let property = homie::sensor_property(&reading.quantity);
+        let topic = homie::property_topic(&self.device_topic, &property);
+        self.send(&topic, &reading.value.to_string())
+    }
+}
+
+impl Publisher for HomiePublisher {
+    fn publish(&mut self, _config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        self.publish_reading(reading)
+    }
+
+    /// Homie has no combined message, every reading goes to its property.
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        for reading in &state.readings {
+            self.publish_reading(reading)?;
+        }
+        Ok(())
+    }
+
+    /// Marks the device as `alert` while the connectivity checks fail or a sensor is unavailable.
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        _consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        self.connectivity_healthy = status.is_healthy();
+        self.send_state()
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        let topic = format!("{}/plant/$name", self.device_topic);
+        self.send(&topic, name)
+    }
+
+    /// Homie has no per-property availability, so an unavailable property loses
+    /// its retained value and the device goes to `alert` until it is back.
+    fn publish_availability(
+        &mut self,
+        _config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        if available {
+            self.unavailable.remove(&quantity);
+        } else {
+            self.unavailable.insert(quantity);
+            // An empty retained message removes the stale value
+            let property = homie::sensor_property(&quantity);
+            let topic = homie::property_topic(&self.device_topic, &property);
+            self.send(&topic, "")?;
+        }
+        self.send_state()
+    }
+
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        let commands = self.connection.take_commands();
+
+        for command in commands.iter().flatten() {
+            let value = match command {
+                Command::SetPlantName(name) => name.clone(),
+                Command::SetSamplingInterval(interval) => interval.as_millis().to_string(),
+                _ => continue,
+            };
+            self.pending_values.push((command.name(), value));
+        }
+
+        commands
+    }
+
+    fn respond(&mut self, response: &CommandResponse) -> Result<(), PublishError> {
+        let Some(index) = self
+            .pending_values
+            .iter()
+            .position(|(command, _)| *command == response.command)
+        else {
+            return Ok(());
+        };
+        let (command, value) = self.pending_values.remove(index);
+
+        if let Err(e) = &response.result {
+            log::warn!("Not setting {}: {}", command, e);
+            return Ok(());
+        }
+
+        let Some(property) = homie::command_property(command) else {
+            return Ok(());
+        };
+        let topic = homie::property_topic(&self.device_topic, property);
+        self.send(&topic, &value)
+    }
+
+    fn poll(&mut self) {
+        self.connection.poll();
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write;
+
+use crate::clock::timestamp::Timestamp;
+
+/// Value of a line protocol field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldValue<'a> {
+    Float(f32),
+    Integer(i64),
+    String(&'a str),
+}
+
+/// Renders one point, e.g. `plant,device=a1b2c3,sensor=soil_moisture value=42.5 1750000000000`,
+/// with the timestamp in milliseconds.
+///
+/// Returns `None` when no field can be represented, e.g. all values are NaN.
+pub fn line(
+    measurement: &str,
+    tags: &[(&str, &str)],
+    fields: &[(&str, FieldValue)],
+    timestamp: Option<Timestamp>,
+) -> Option<String> {
+    let mut line = escape(measurement, &[',', ' ']);
+
+    // Empty tag values are not allowed
+    for (key, value) in tags.iter().filter(|(_, value)| !value.is_empty()) {
+        let _ = write!(
+            line,
+            ",{}={}",
+            escape(key, &[',', '=', ' ']),
+            escape(value, &[',', '=', ' '])
+        );
+    }
+
+    let fields: Vec<String> = fields
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                FieldValue::Float(value) if value.is_finite() => value.to_string(),
+                FieldValue::Float(_) => return None,
+                FieldValue::Integer(value) => format!("{}i", value),
+                FieldValue::String(value) => format!("\"{}\"", escape(value, &['"'])),
+            };
+            Some(format!("{}={}", escape(key, &[',', '=', ' ']), value))
+        })
+        .collect();
+    if fields.is_empty() {
+        return None;
+    }
+
+    let _ = write!(line, " {}", fields.join(","));
+    if let Some(timestamp) = timestamp {
+        let _ = write!(line, " {}", timestamp.unix_millis());
+    }
+
+    Some(line)
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Percent-encodes a query parameter value.
+pub fn query_escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_a_point() {
+        let line = line(
+            "plant",
+            &[("device", "a1b2c3"), ("sensor", "soil_moisture")],
+            &[
+                ("value", FieldValue::Float(42.5)),
+                ("unit", FieldValue::String("%")),
+                ("failures", FieldValue::Integer(3)),
+            ],
+            Some(Timestamp::from_unix_millis(1_750_000_000_000)),
+        );
+
+        assert_eq!(
+            line.as_deref(),
+            Some(
+                "plant,device=a1b2c3,sensor=soil_moisture value=42.5,unit=\"%\",failures=3i 1750000000000"
+            )
+        );
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        let line = line(
+            "my plant",
+            &[("plant", "Monstera, big=1"), ("empty", "")],
+            &[("note", FieldValue::String("say \"hi\""))],
+            None,
+        );
+
+        assert_eq!(
+            line.as_deref(),
+            Some("my\\ plant,plant=Monstera\\,\\ big\\=1 note=\"say \\\"hi\\\"\"")
+        );
+    }
+
+    #[test]
+    fn skips_points_without_representable_fields() {
+        let line = line(
+            "plant",
+            &[],
+            &[("value", FieldValue::Float(f32::NAN))],
+            None,
+        );
+
+        assert_eq!(line, None);
+    }
+
+    #[test]
+    fn percent_encodes_query_values() {
+        assert_eq!(query_escape("my org/1"), "my%20org%2F1");
+        assert_eq!(query_escape("plants-2024_a.b~"), "plants-2024_a.b~");
+    }
+}
@@ -0,0 +1,425 @@
+pub mod line_protocol;
+
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::clock::timestamp::Timestamp;
+use crate::config::InfluxDbConfig;
+use crate::health::status::ConnectivityStatus;
+use crate::http_client::{HttpClient, HttpError, RetryBackoff};
+use crate::sensor::Reading;
+use line_protocol::{line, query_escape, FieldValue};
+
+/// TLS handshakes of `https://` servers need a large stack.
+const WRITER_STACK_SIZE: usize = 12 * 1024;
+
+/// Writes readings to InfluxDB 2 in line protocol. Lines are sent in batches
+/// by a thread of its own, so a slow or unreachable server does not block the
+/// sensor loop. Failed batches are retried with an exponential backoff.
+pub struct InfluxDbPublisher {
+    batches: Sender<String>,
+    results: Receiver<Result<u16, HttpError>>,
+    measurement: String,
+    device_id: String,
+    plant: String,
+    batch_size: usize,
+    max_pending: usize,
+    flush_interval: Duration,
+    pending: VecDeque<String>,
+    /// Lines at the front of `pending` the writer is sending.
+    in_flight: usize,
+    last_flush: Instant,
+    retry: RetryBackoff,
+    /// Error of the last write, cleared by the next successful one.
+    last_error: Option<HttpError>,
+}
+
+impl InfluxDbPublisher {
+    pub fn new<C: HttpClient + Send + 'static>(
+        client: C,
+        config: &InfluxDbConfig,
+        device_id: &str,
+        plant: &str,
+    ) -> Self {
+        let write_url = format!(
+            "{}/api/v2/write?org={}&bucket={}&precision=ms",
+            config.url.trim_end_matches('/'),
+            query_escape(&config.org),
+            query_escape(&config.bucket)
+        );
+        let (batches, results) = spawn_writer(client, write_url, format!("Token {}", config.token));
+
+        Self {
+            batches,
+            results,
+            measurement: config.measurement.clone(),
+            device_id: device_id.to_string(),
+            plant: plant.to_string(),
+            batch_size: config.batch_size.max(1),
+            max_pending: config.max_pending.max(1),
+            flush_interval: Duration::from_secs(config.flush_interval_secs),
+            pending: VecDeque::new(),
+            in_flight: 0,
+            last_flush: Instant::now(),
+            retry: RetryBackoff::new(),
+            last_error: None,
+        }
+    }
+
+    /// Queues `line`. The result reports whether InfluxDB takes the lines, they
+    /// are kept for a later batch either way.
+    fn queue(&mut self, line: Option<String>) -> Result<(), PublishError> {
+        if let Some(line) = line {
+            if self.pending.len() >= self.max_pending {
+                if self.pending.len() > self.in_flight {
+                    log::warn!("InfluxDB queue is full, dropping the oldest line");
+                    self.pending.remove(self.in_flight);
+                } else {
+                    log::warn!("InfluxDB queue is full, dropping the newest line");
+                    return self.status();
+                }
+            }
+            self.pending.push_back(line);
+        }
+
+        if self.pending.len() >= self.batch_size {
+            self.flush();
+        }
+        self.status()
+    }
+
+    fn status(&self) -> Result<(), PublishError> {
+        match &self.last_error {
+            Some(e) => Err(PublishError::Http(e.clone())),
+            None => Ok(()),
+        }
+    }
+
+    fn reading_line(&self, reading: &Reading) -> Option<String> {
+        line(
+            &self.measurement,
+            &[
+                ("device", &self.device_id),
+                ("plant", &self.plant),
+                ("sensor", reading.quantity.key()),
+            ],
+            &[
+                ("value", FieldValue::Float(reading.value)),
+                ("unit", FieldValue::String(reading.unit())),
+            ],
+            reading.timestamp,
+        )
+    }
+
+    /// Hands the oldest batch to the writer unless one is still being sent or
+    /// a retry is not due yet.
+    fn flush(&mut self) {
+        let now = Instant::now();
+        if self.in_flight > 0 || self.pending.is_empty() || !self.retry.is_due(now) {
+            return;
+        }
+        self.last_flush = now;
+
+        let count = self.pending.len().min(self.batch_size);
+        let body = self
+            .pending
+            .iter()
+            .take(count)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        match self.batches.send(body) {
+            Ok(()) => self.in_flight = count,
+            Err(_) => self.writer_stopped(),
+        }
+    }
+
+    /// Takes the result of the batch being sent, waiting for it if `block`.
+    fn collect(&mut self, block: bool) {
+        if self.in_flight == 0 {
+            return;
+        }
+
+        let result = if block {
+            self.results.recv().ok()
+        } else {
+            match self.results.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => None,
+            }
+        };
+        let Some(result) = result else {
+            self.writer_stopped();
+            return;
+        };
+
+        let count = mem::take(&mut self.in_flight);
+        match result {
+            Ok(_) => {
+                log::info!("Wrote {} lines to InfluxDB", count);
+                self.pending.drain(..count);
+                self.retry.succeeded();
+                self.last_error = None;
+            }
+            Err(e) if e.is_retryable() => {
+                let delay = self.retry.failed(Instant::now());
+                log::warn!("{}, retrying in {:?}", e, delay);
+                self.last_error = Some(e);
+            }
+            Err(e) => {
+                // The server rejected the data, sending it again will not help
+                log::error!("{}, dropping {} lines", e, count);
+                self.pending.drain(..count);
+                self.last_error = Some(e);
+            }
+        }
+    }
+
+    fn writer_stopped(&mut self) {
+        log::error!("InfluxDB writer stopped");
+        self.in_flight = 0;
+        self.last_error = Some(HttpError::Transport("writer stopped".to_string()));
+    }
+}
+
+/// Starts the thread posting batches to `url`, it answers every batch with the result.
+fn spawn_writer<C: HttpClient + Send + 'static>(
+    mut client: C,
+    url: String,
+    authorization: String,
+) -> (Sender<String>, Receiver<Result<u16, HttpError>>) {
+    let (batches, batch_receiver) = mpsc::channel::<String>();
+    let (result_sender, results) = mpsc::channel();
+
+    let spawned = thread::Builder::new()
+        .name("influxdb".to_string())
+        .stack_size(WRITER_STACK_SIZE)
+        .spawn(move || {
+            let headers = [
+                ("Authorization", authorization.as_str()),
+                ("Content-Type", "text/plain; charset=utf-8"),
+            ];
+            for body in batch_receiver {
+                let result = client.post(&url, &headers, body.as_bytes());
+                if result_sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        // The publisher notices the closed channel and reports every write as failed
+        log::error!("Failed to start InfluxDB writer: {}", e);
+    }
+
+    (batches, results)
+}
+
+impl Publisher for InfluxDbPublisher {
+    fn publish(&mut self, _config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        let line = self.reading_line(reading);
+        self.queue(line)
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        let mut result = self.status();
+        for reading in &state.readings {
+            let line = self.reading_line(reading);
+            result = self.queue(line);
+        }
+        result
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        let (gateway, broker, dns) = (
+            status.gateway.to_string(),
+            status.broker.to_string(),
+            status.dns.to_string(),
+        );
+        let line = line(
+            "connectivity",
+            &[("device", &self.device_id), ("plant", &self.plant)],
+            &[
+                ("gateway", FieldValue::String(&gateway)),
+                ("broker", FieldValue::String(&broker)),
+                ("dns", FieldValue::String(&dns)),
+                (
+                    "consecutive_failures",
+                    FieldValue::Integer(consecutive_failures.into()),
+                ),
+            ],
+            Timestamp::from_system_time(SystemTime::now()),
+        );
+        self.queue(line)
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        self.plant = name.to_string();
+        Ok(())
+    }
+
+    fn poll(&mut self) {
+        self.collect(false);
+
+        let due = self.pending.len() >= self.batch_size
+            || self.last_flush.elapsed() >= self.flush_interval;
+        if !self.pending.is_empty() && due {
+            self.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::StubHttpClient;
+    use crate::publisher::change_filter::{Deadband, ReportPolicy};
+    use crate::publisher::sensor_config::QoS;
+    use crate::sensor::Quantity;
+
+    fn publisher(batch_size: usize) -> (InfluxDbPublisher, StubHttpClient) {
+        let config = InfluxDbConfig {
+            url: "http://influx.local:8086/".to_string(),
+            org: "home".to_string(),
+            bucket: "plants".to_string(),
+            token: "secret".to_string(),
+            measurement: "plant".to_string(),
+            batch_size,
+            flush_interval_secs: 30,
+            max_pending: 4,
+        };
+        let client = StubHttpClient::new();
+        let publisher = InfluxDbPublisher::new(client.clone(), &config, "a1b2c3", "Monstera");
+        (publisher, client)
+    }
+
+    fn config() -> SensorConfig {
+        SensorConfig {
+            topic: "plant/soil_moisture".to_string(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+            report: ReportPolicy {
+                deadband: Deadband::Absolute(1.0),
+                heartbeat: Duration::from_secs(60),
+            },
+        }
+    }
+
+    fn reading(value: f32) -> Reading {
+        Reading {
+            quantity: Quantity::SoilMoisture,
+            value,
+            timestamp: Some(Timestamp::from_unix_millis(1000)),
+        }
+    }
+
+    #[test]
+    fn writes_full_batches() {
+        let (mut publisher, client) = publisher(2);
+
+        publisher.publish(&config(), &reading(1.0)).unwrap();
+        publisher.collect(true);
+        assert!(client.requests().is_empty());
+        publisher.publish(&config(), &reading(2.0)).unwrap();
+        publisher.collect(true);
+
+        let request = &client.requests()[0];
+        assert_eq!(
+            request.url,
+            "http://influx.local:8086/api/v2/write?org=home&bucket=plants&precision=ms"
+        );
+        assert_eq!(request.header("Authorization"), Some("Token secret"));
+        assert_eq!(
+            request.body,
+            "plant,device=a1b2c3,plant=Monstera,sensor=soil_moisture value=1,unit=\"%\" 1000\n\
+             plant,device=a1b2c3,plant=Monstera,sensor=soil_moisture value=2,unit=\"%\" 1000"
+        );
+        assert!(publisher.pending.is_empty());
+    }
+
+    #[test]
+    fn reports_failed_writes_and_keeps_their_lines() {
+        let (mut publisher, client) = publisher(1);
+        client.respond_with(Err(HttpError::Status(503)));
+
+        publisher.publish(&config(), &reading(1.0)).unwrap();
+        publisher.collect(true);
+
+        // The second reading waits for the backoff
+        assert!(matches!(
+            publisher.publish(&config(), &reading(2.0)),
+            Err(PublishError::Http(HttpError::Status(503)))
+        ));
+        assert_eq!(client.requests().len(), 1);
+        assert_eq!(publisher.pending.len(), 2);
+    }
+
+    #[test]
+    fn drops_rejected_lines() {
+        let (mut publisher, client) = publisher(1);
+        client.respond_with(Err(HttpError::Status(400)));
+
+        publisher.publish(&config(), &reading(1.0)).unwrap();
+        publisher.collect(true);
+
+        assert!(publisher.pending.is_empty());
+        assert!(publisher.status().is_err());
+    }
+
+    #[test]
+    fn success_clears_the_error() {
+        let (mut publisher, client) = publisher(1);
+        client.respond_with(Err(HttpError::Status(400)));
+
+        publisher.publish(&config(), &reading(1.0)).unwrap();
+        publisher.collect(true);
+        assert!(publisher.publish(&config(), &reading(2.0)).is_err());
+        publisher.collect(true);
+
+        assert!(publisher.status().is_ok());
+        assert_eq!(client.requests().len(), 2);
+    }
+
+    #[test]
+    fn drops_the_oldest_line_when_full() {
+        let (mut publisher, _client) = publisher(10);
+
+        for value in 1..=5 {
+            publisher
+                .publish(&config(), &reading(value as f32))
+                .unwrap();
+        }
+
+        assert_eq!(publisher.pending.len(), 4);
+        assert!(publisher.pending[0].contains("value=2"));
+    }
+
+    #[test]
+    fn keeps_lines_being_sent_when_full() {
+        let (mut publisher, _client) = publisher(2);
+
+        for value in 1..=5 {
+            publisher
+                .publish(&config(), &reading(value as f32))
+                .unwrap();
+        }
+
+        // Lines 1 and 2 are with the writer, 3 made room for 5
+        assert_eq!(publisher.in_flight, 2);
+        assert!(publisher.pending[0].contains("value=1"));
+        assert!(publisher.pending[2].contains("value=4"));
+        publisher.collect(true);
+        assert_eq!(publisher.pending.len(), 2);
+    }
+}
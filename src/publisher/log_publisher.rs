@@ -0,0 +1,58 @@
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::health::status::ConnectivityStatus;
+use crate::sensor::{Quantity, Reading};
+
+/// Writes the readings to the serial console log, never fails.
+pub struct LogPublisher;
+
+impl Publisher for LogPublisher {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        log::info!(
+            "{} = {} {} ({})",
+            config.topic,
+            reading.value,
+            reading.unit(),
+            reading.quantity.key()
+        );
+        Ok(())
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        log::info!("State {}", state.to_json());
+        Ok(())
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        log::info!(
+            "Connectivity {:?}, {} consecutive failures",
+            status,
+            consecutive_failures
+        );
+        Ok(())
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        log::info!("Plant name {}", name);
+        Ok(())
+    }
+
+    fn publish_availability(
+        &mut self,
+        _config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        if available {
+            log::info!("{} available", quantity.key());
+        } else {
+            log::warn!("{} unavailable", quantity.key());
+        }
+        Ok(())
+    }
+}
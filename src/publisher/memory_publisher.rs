@@ -0,0 +1,105 @@
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::health::status::ConnectivityStatus;
+use crate::http_client::HttpError;
+use crate::sensor::{Quantity, Reading};
+
+/// Keeps everything published in memory, for exercising the sensor loop on the host.
+#[derive(Default)]
+pub struct MemoryPublisher {
+    pub readings: Vec<(String, Reading)>,
+    pub states: Vec<StateMessage>,
+    pub connectivity: Vec<ConnectivityStatus>,
+    pub plant_names: Vec<String>,
+    pub availability: Vec<(Quantity, bool)>,
+    /// Commands handed to the sensor loop on the next call to [`Publisher::commands`].
+    pub commands: Vec<Result<Command, CommandError>>,
+    pub responses: Vec<CommandResponse>,
+    /// Simulates a lost connection, publishing fails with [`PublishError::NotConnected`].
+    pub offline: bool,
+    /// Simulates a backend refusing readings and state messages with HTTP 400.
+    pub refuse: bool,
+}
+
+impl MemoryPublisher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Publisher for MemoryPublisher {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        if self.offline {
+            return Err(PublishError::NotConnected);
+        }
+        if self.refuse {
+            return Err(PublishError::Http(HttpError::Status(400)));
+        }
+
+        self.readings.push((config.topic.clone(), reading.clone()));
+        Ok(())
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        if self.offline {
+            return Err(PublishError::NotConnected);
+        }
+        if self.refuse {
+            return Err(PublishError::Http(HttpError::Status(400)));
+        }
+
+        self.states.push(state.clone());
+        Ok(())
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        _consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        if self.offline {
+            return Err(PublishError::NotConnected);
+        }
+
+        self.connectivity.push(*status);
+        Ok(())
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        if self.offline {
+            return Err(PublishError::NotConnected);
+        }
+
+        self.plant_names.push(name.to_string());
+        Ok(())
+    }
+
+    fn publish_availability(
+        &mut self,
+        _config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        if self.offline {
+            return Err(PublishError::NotConnected);
+        }
+
+        self.availability.push((quantity, available));
+        Ok(())
+    }
+
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        std::mem::take(&mut self.commands)
+    }
+
+    fn respond(&mut self, response: &CommandResponse) -> Result<(), PublishError> {
+        if self.offline {
+            return Err(PublishError::NotConnected);
+        }
+
+        self.responses.push(response.clone());
+        Ok(())
+    }
+}
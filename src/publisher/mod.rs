@@ -1,2 +1,152 @@
+pub mod buffer;
+pub mod buffered_publisher;
+pub mod change_filter;
+pub mod discovery;
+pub mod fan_out;
+pub mod home_assistant_api;
+pub mod homie;
+#[cfg(target_os = "espidf")]
+pub mod homie_publisher;
+pub mod influxdb;
+pub mod log_publisher;
+pub mod memory_publisher;
+#[cfg(target_os = "espidf")]
+pub mod mqtt_connection;
+#[cfg(target_os = "espidf")]
 pub mod mqtt_publisher;
-pub mod sensor_config;
\ No newline at end of file
+pub mod mqtt_security;
+pub mod sensor_config;
+pub mod state;
+pub mod unavailable_publisher;
+
+use std::fmt;
+
+#[cfg(target_os = "espidf")]
+use esp_idf_hal::sys::EspError;
+
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::health::status::ConnectivityStatus;
+use crate::http_client::HttpError;
+use crate::sensor::{Quantity, Reading};
+use sensor_config::SensorConfig;
+use state::StateMessage;
+
+#[derive(Debug)]
+pub enum PublishError {
+    /// The backend has no connection, nothing was sent.
+    NotConnected,
+    #[cfg(target_os = "espidf")]
+    Mqtt(EspError),
+    Http(HttpError),
+}
+
+impl PublishError {
+    /// Whether publishing the same message again may succeed, the backend
+    /// refuses the message itself otherwise.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PublishError::NotConnected => true,
+            #[cfg(target_os = "espidf")]
+            PublishError::Mqtt(_) => true,
+            PublishError::Http(e) => e.is_retryable(),
+        }
+    }
+}
+
+impl fmt::Display for PublishError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishError::NotConnected => write!(f, "not connected"),
+            #[cfg(target_os = "espidf")]
+            PublishError::Mqtt(e) => write!(f, "MQTT publish failed: {}", e),
+            PublishError::Http(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PublishError {}
+
+/// Destination of the sensor readings and device status.
+pub trait Publisher {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError>;
+
+    /// Publishes the readings of one cycle as a single message.
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError>;
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError>;
+
+    fn publish_plant_name(&mut self, _name: &str) -> Result<(), PublishError> {
+        Ok(())
+    }
+
+    /// Announces whether `quantity` can be measured, it is unavailable while
+    /// its sensor fails.
+    fn publish_availability(
+        &mut self,
+        _config: &SensorConfig,
+        _quantity: Quantity,
+        _available: bool,
+    ) -> Result<(), PublishError> {
+        Ok(())
+    }
+
+    /// Returns the remote commands received since the last call.
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        Vec::new()
+    }
+
+    /// Answers a command returned by [`Publisher::commands`].
+    fn respond(&mut self, _response: &CommandResponse) -> Result<(), PublishError> {
+        Ok(())
+    }
+
+    /// Called once per sensor loop cycle, e.g. to re-announce the device after a reconnect.
+    fn poll(&mut self) {}
+}
+
+impl<P: Publisher + ?Sized> Publisher for Box<P> {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        (**self).publish(config, reading)
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        (**self).publish_state(state)
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        (**self).publish_connectivity(status, consecutive_failures)
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        (**self).publish_plant_name(name)
+    }
+
+    fn publish_availability(
+        &mut self,
+        config: &SensorConfig,
+        quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        (**self).publish_availability(config, quantity, available)
+    }
+
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        (**self).commands()
+    }
+
+    fn respond(&mut self, response: &CommandResponse) -> Result<(), PublishError> {
+        (**self).respond(response)
+    }
+
+    fn poll(&mut self) {
+        (**self).poll()
+    }
+}
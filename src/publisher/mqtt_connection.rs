@@ -0,0 +1,252 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use esp_idf_hal::sys::EspError;
+use esp_idf_svc::mqtt::client::{
+    Details, EspMqttClient, EventPayload, LwtConfiguration, MqttClientConfiguration, QoS,
+};
+
+use esp_idf_svc::sys::esp_crt_bundle_attach;
+use esp_idf_svc::tls::X509;
+
+use super::homie::{self, DeviceState};
+use super::mqtt_security::MqttSecurity;
+use crate::command::{command_name, Command, CommandError, CommandResponse};
+
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+/// Commands received between two polls beyond this are dropped.
+const MAX_PENDING_COMMANDS: usize = 16;
+
+/// Where the availability is announced and commands are received.
+pub enum TopicLayout {
+    /// "online" / "offline" on the Home Assistant availability topic, commands
+    /// on `<base>/cmd/<name>` answered on `<base>/response/<name>`.
+    HomeAssistant {
+        availability_topic: String,
+        base_topic: String,
+    },
+    /// Homie 4 `$state` "ready" / "lost", commands on the `/set` topics of the
+    /// settable properties.
+    Homie { device_topic: String },
+}
+
+impl TopicLayout {
+    fn availability_topic(&self) -> String {
+        match self {
+            TopicLayout::HomeAssistant {
+                availability_topic, ..
+            } => availability_topic.clone(),
+            TopicLayout::Homie { device_topic } => homie::state_topic(device_topic),
+        }
+    }
+
+    fn online(&self) -> &'static str {
+        match self {
+            TopicLayout::HomeAssistant { .. } => ONLINE,
+            TopicLayout::Homie { .. } => DeviceState::Ready.as_str(),
+        }
+    }
+
+    fn offline(&self) -> &'static str {
+        match self {
+            TopicLayout::HomeAssistant { .. } => OFFLINE,
+            TopicLayout::Homie { .. } => DeviceState::Lost.as_str(),
+        }
+    }
+
+    fn command_filter(&self) -> String {
+        match self {
+            TopicLayout::HomeAssistant { base_topic, .. } => format!("{}/cmd/#", base_topic),
+            TopicLayout::Homie { device_topic } => homie::set_filter(device_topic),
+        }
+    }
+
+    fn command_name<'a>(&self, topic: &'a str) -> Option<&'a str> {
+        match self {
+            TopicLayout::HomeAssistant { base_topic, .. } => {
+                command_name(&format!("{}/cmd", base_topic), topic)
+            }
+            TopicLayout::Homie { device_topic } => homie::set_command(device_topic, topic),
+        }
+    }
+
+    /// Homie has no responses, the new value of the property is published instead.
+    fn response_topic(&self, command: &str) -> Option<String> {
+        match self {
+            TopicLayout::HomeAssistant { base_topic, .. } => {
+                Some(format!("{}/response/{}", base_topic, command))
+            }
+            TopicLayout::Homie { .. } => None,
+        }
+    }
+}
+
+/// MQTT client announcing the device's availability: a retained "online" birth
+/// message after every (re)connection and a retained "offline" last will.
+///
+/// It also subscribes to the command topics of the [`TopicLayout`].
+pub struct MqttConnection {
+    client: EspMqttClient<'static>,
+    availability_topic: String,
+    layout: TopicLayout,
+    /// Birth message, the layout's online payload unless changed with [`Self::set_online`].
+    online: &'static str,
+    connected: Arc<AtomicBool>,
+    birth_pending: Arc<AtomicBool>,
+    commands: Arc<Mutex<Vec<(String, Vec<u8>)>>>,
+    /// The client keeps pointers to the certificates for reconnecting.
+    _security: MqttSecurity,
+}
+
+impl MqttConnection {
+    pub fn connect(
+        url: &str,
+        security: MqttSecurity,
+        layout: TopicLayout,
+    ) -> Result<Self, EspError> {
+        let availability_topic = layout.availability_topic();
+        let connected = Arc::new(AtomicBool::new(false));
+        let birth_pending = Arc::new(AtomicBool::new(false));
+        let commands = Arc::new(Mutex::new(Vec::new()));
+
+        let client = {
+            let connected = connected.clone();
+            let birth_pending = birth_pending.clone();
+            let commands = commands.clone();
+
+            EspMqttClient::new_cb(
+                url,
+                &MqttClientConfiguration {
+                    network_timeout: Duration::from_secs(5),
+                    client_id: security.client_id.as_deref(),
+                    username: security.username.as_deref(),
+                    password: security.password.as_deref(),
+                    server_certificate: security.ca_certificate.as_deref().map(X509::pem),
+                    client_certificate: security
+                        .client_identity
+                        .as_ref()
+                        .map(|(cert, _)| X509::pem(cert)),
+                    private_key: security
+                        .client_identity
+                        .as_ref()
+                        .map(|(_, key)| X509::pem(key)),
+                    crt_bundle_attach: (url.starts_with("mqtts://")
+                        && security.ca_certificate.is_none())
+                    .then_some(esp_crt_bundle_attach),
+                    lwt: Some(LwtConfiguration {
+                        topic: &availability_topic,
+                        payload: layout.offline().as_bytes(),
+                        qos: QoS::AtLeastOnce,
+                        retain: true,
+                    }),
+                    ..Default::default()
+                },
+                move |event| {
+                    log::info!("MQTT Event: {:?}", event.payload());
+
+                    match event.payload() {
+                        EventPayload::Connected(_) => {
+                            connected.store(true, Ordering::Relaxed);
+                            birth_pending.store(true, Ordering::Relaxed);
+                        }
+                        EventPayload::Disconnected => connected.store(false, Ordering::Relaxed),
+                        EventPayload::Received {
+                            topic: Some(topic),
+                            data,
+                            details: Details::Complete,
+                            ..
+                        } => {
+                            let mut commands = commands.lock().unwrap();
+                            if commands.len() < MAX_PENDING_COMMANDS {
+                                commands.push((topic.to_string(), data.to_vec()));
+                            } else {
+                                log::warn!("Dropping command on {}, too many pending", topic);
+                            }
+                        }
+                        _ => {}
+                    }
+                },
+            )?
+        };
+
+        Ok(Self {
+            client,
+            availability_topic,
+            online: layout.online(),
+            layout,
+            connected,
+            birth_pending,
+            commands,
+            _security: security,
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub fn client(&mut self) -> &mut EspMqttClient<'static> {
+        &mut self.client
+    }
+
+    /// Returns the commands received since the last call.
+    pub fn take_commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        let received = std::mem::take(&mut *self.commands.lock().unwrap());
+
+        received
+            .into_iter()
+            .filter_map(|(topic, payload)| {
+                let name = self.layout.command_name(&topic)?;
+                Some(Command::parse(name, &payload))
+            })
+            .collect()
+    }
+
+    pub fn respond(&mut self, response: &CommandResponse) -> Result<(), EspError> {
+        let Some(topic) = self.layout.response_topic(&response.command) else {
+            return Ok(());
+        };
+        self.client.publish(
+            &topic,
+            QoS::AtLeastOnce,
+            false,
+            response.to_json().as_bytes(),
+        )?;
+
+        Ok(())
+    }
+
+    /// Sets the birth message published after the next reconnection.
+    pub fn set_online(&mut self, payload: &'static str) {
+        self.online = payload;
+    }
+
+    /// Subscribes to the command topics and publishes the birth message if the
+    /// client (re)connected since the last call.
+    pub fn poll(&mut self) {
+        if !self.birth_pending.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        let command_filter = self.layout.command_filter();
+        match self.client.subscribe(&command_filter, QoS::AtLeastOnce) {
+            Ok(_) => log::info!("Subscribed to {}", command_filter),
+            Err(e) => log::error!("Error subscribing to {}: {:?}", command_filter, e),
+        }
+
+        match self.client.publish(
+            &self.availability_topic,
+            QoS::AtLeastOnce,
+            true,
+            self.online.as_bytes(),
+        ) {
+            Ok(_) => log::info!("Published availability to {}", self.availability_topic),
+            Err(e) => {
+                log::error!("Error publishing availability: {:?}", e);
+                self.birth_pending.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
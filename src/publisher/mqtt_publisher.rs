@@ -1,26 +1,137 @@
-use esp_idf_hal::sys::EspError;
-use esp_idf_svc::mqtt::client::{EspMqttClient, QoS};
+use esp_idf_svc::mqtt::client::QoS;
 
-trait Publisher {
-    fn publish(&mut self, payload: String) -> Result<(), EspError>;
+use super::mqtt_connection::{MqttConnection, OFFLINE, ONLINE};
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::health::status::ConnectivityStatus;
+use crate::sensor::{Quantity, Reading};
+
+/// Retained plant name of the device under its base topic.
+pub fn plant_name_topic(base_topic: &str) -> String {
+    format!("{}/plant_name", base_topic)
 }
 
-pub struct MqttPublisher<'a> {
-    client: EspMqttClient<'a>,
-    topic: String,
+/// Retained result of the last connectivity check under the base topic.
+fn connectivity_topic(base_topic: &str) -> String {
+    format!("{}/connectivity", base_topic)
 }
 
-impl<'a> MqttPublisher<'a> {
-    pub fn new(client: EspMqttClient<'a>, topic: String) -> Self {
-        Self { client, topic }
-    }
+/// Publishes each reading as `{"value": x, "timestamp": "..."}` to the sensor's topic.
+pub struct MqttPublisher {
+    connection: MqttConnection,
+    base_topic: String,
+    /// Topic, QoS and retain flag of the batched state message.
+    state: SensorConfig,
 }
 
-impl<'a> Publisher for MqttPublisher<'a> {
-    fn publish(&mut self, payload: String) -> Result<(), EspError> {
-        self.client
-            .publish(&self.topic, QoS::AtMostOnce, false, payload.as_bytes())?;
+impl MqttPublisher {
+    pub fn new(connection: MqttConnection, base_topic: String, state: SensorConfig) -> Self {
+        Self {
+            connection,
+            base_topic,
+            state,
+        }
+    }
+
+    fn send(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retain: bool,
+        payload: &str,
+    ) -> Result<(), PublishError> {
+        if !self.connection.is_connected() {
+            return Err(PublishError::NotConnected);
+        }
+
+        let id = self
+            .connection
+            .client()
+            .publish(topic, qos, retain, payload.as_bytes())
+            .map_err(PublishError::Mqtt)?;
+        log::info!("Published message with id {}", id);
 
         Ok(())
     }
 }
+
+impl Publisher for MqttPublisher {
+    fn publish(&mut self, config: &SensorConfig, reading: &Reading) -> Result<(), PublishError> {
+        self.send(
+            &config.topic,
+            config.qos,
+            config.retain,
+            &reading_payload(reading),
+        )
+    }
+
+    fn publish_state(&mut self, state: &StateMessage) -> Result<(), PublishError> {
+        let (topic, qos, retain) = (self.state.topic.clone(), self.state.qos, self.state.retain);
+        self.send(&topic, qos, retain, &state.to_json())
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        status: &ConnectivityStatus,
+        consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        let topic = connectivity_topic(&self.base_topic);
+        self.send(
+            &topic,
+            QoS::AtMostOnce,
+            true,
+            &status.to_json(consecutive_failures),
+        )
+    }
+
+    fn publish_plant_name(&mut self, name: &str) -> Result<(), PublishError> {
+        let topic = plant_name_topic(&self.base_topic);
+        self.send(&topic, QoS::AtLeastOnce, true, name)
+    }
+
+    fn publish_availability(
+        &mut self,
+        config: &SensorConfig,
+        _quantity: Quantity,
+        available: bool,
+    ) -> Result<(), PublishError> {
+        let payload = if available { ONLINE } else { OFFLINE };
+        self.send(
+            &config.availability_topic(),
+            QoS::AtLeastOnce,
+            true,
+            payload,
+        )
+    }
+
+    fn commands(&mut self) -> Vec<Result<Command, CommandError>> {
+        self.connection.take_commands()
+    }
+
+    fn respond(&mut self, response: &CommandResponse) -> Result<(), PublishError> {
+        if !self.connection.is_connected() {
+            return Err(PublishError::NotConnected);
+        }
+
+        self.connection
+            .respond(response)
+            .map_err(PublishError::Mqtt)
+    }
+
+    fn poll(&mut self) {
+        self.connection.poll();
+    }
+}
+
+fn reading_payload(reading: &Reading) -> String {
+    match reading.timestamp {
+        Some(timestamp) => format!(
+            "{{\"value\": {}, \"timestamp\": \"{}\"}}",
+            reading.value,
+            timestamp.to_iso8601()
+        ),
+        None => format!("{{\"value\": {}}}", reading.value),
+    }
+}
@@ -1,3 +1,208 @@
+use std::fmt;
+use std::time::Duration;
+
+use super::change_filter::ReportPolicy;
+use crate::config::SensorPublishConfig;
+
+#[cfg(target_os = "espidf")]
+pub use esp_idf_svc::mqtt::client::QoS;
+
+/// MQTT delivery guarantee, mirrors the ESP-IDF type on the host.
+#[cfg(not(target_os = "espidf"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QoS {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+/// Where and how the readings of one sensor are published.
+#[derive(Clone)]
 pub struct SensorConfig {
     pub topic: String,
-}
\ No newline at end of file
+    pub qos: QoS,
+    pub retain: bool,
+    pub report: ReportPolicy,
+}
+
+impl SensorConfig {
+    pub fn new(config: &SensorPublishConfig, vars: &TopicVariables) -> Result<Self, TopicError> {
+        Ok(Self {
+            topic: render_topic(&config.topic_template, vars)?,
+            qos: qos_from_level(config.qos)?,
+            retain: config.retain,
+            report: report_policy(config),
+        })
+    }
+
+    /// Settings of the batched state message, published on `<base>/state`.
+    pub fn state(config: &SensorPublishConfig, base_topic: &str) -> Result<Self, TopicError> {
+        Ok(Self {
+            topic: format!("{}/state", base_topic),
+            qos: qos_from_level(config.qos)?,
+            retain: config.retain,
+            report: report_policy(config),
+        })
+    }
+
+    /// Topic with the "online" / "offline" availability of the readings.
+    pub fn availability_topic(&self) -> String {
+        format!("{}/availability", self.topic)
+    }
+}
+
+fn qos_from_level(qos: u8) -> Result<QoS, TopicError> {
+    match qos {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        qos => Err(TopicError::InvalidQos(qos)),
+    }
+}
+
+fn report_policy(config: &SensorPublishConfig) -> ReportPolicy {
+    ReportPolicy {
+        deadband: config.deadband,
+        heartbeat: Duration::from_secs(config.heartbeat_secs),
+    }
+}
+
+/// Values substituted into topic templates.
+pub struct TopicVariables<'a> {
+    pub device_id: &'a str,
+    pub plant: &'a str,
+    pub sensor: &'a str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TopicError {
+    UnknownPlaceholder(String),
+    UnclosedPlaceholder,
+    /// Published topics must not be empty nor contain the `+` and `#` wildcards.
+    InvalidTopic(String),
+    InvalidQos(u8),
+}
+
+impl fmt::Display for TopicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopicError::UnknownPlaceholder(name) => {
+                write!(f, "unknown topic placeholder {{{}}}", name)
+            }
+            TopicError::UnclosedPlaceholder => write!(f, "unclosed placeholder in topic template"),
+            TopicError::InvalidTopic(topic) => write!(f, "invalid MQTT topic {:?}", topic),
+            TopicError::InvalidQos(qos) => write!(f, "invalid QoS {}, expected 0, 1 or 2", qos),
+        }
+    }
+}
+
+impl std::error::Error for TopicError {}
+
+/// Expands `{device_id}`, `{plant}` and `{sensor}` in a topic template, e.g.
+/// `plant-doctor/{device_id}/{sensor}`.
+pub fn render_topic(template: &str, vars: &TopicVariables) -> Result<String, TopicError> {
+    let mut topic = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        topic.push_str(&rest[..start]);
+
+        let (name, after) = rest[start + 1..]
+            .split_once('}')
+            .ok_or(TopicError::UnclosedPlaceholder)?;
+        match name {
+            "device_id" => topic.push_str(&topic_segment(vars.device_id)),
+            "plant" => topic.push_str(&topic_segment(vars.plant)),
+            "sensor" => topic.push_str(&topic_segment(vars.sensor)),
+            _ => return Err(TopicError::UnknownPlaceholder(name.to_string())),
+        }
+
+        rest = after;
+    }
+    topic.push_str(rest);
+
+    if topic.is_empty() || topic.contains(['+', '#']) {
+        return Err(TopicError::InvalidTopic(topic));
+    }
+
+    Ok(topic)
+}
+
+/// Turns a free-form value such as a plant name into a single topic level,
+/// e.g. `Monstera deliciosa` into `monstera_deliciosa`.
+fn topic_segment(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '+' | '#' => '_',
+            c if c.is_whitespace() => '_',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: TopicVariables = TopicVariables {
+        device_id: "a1b2c3",
+        plant: "Monstera deliciosa",
+        sensor: "soil_moisture",
+    };
+
+    #[test]
+    fn renders_each_placeholder() {
+        assert_eq!(
+            render_topic("plant-doctor/{device_id}", &VARS).unwrap(),
+            "plant-doctor/a1b2c3"
+        );
+        assert_eq!(
+            render_topic("plants/{plant}/state", &VARS).unwrap(),
+            "plants/monstera_deliciosa/state"
+        );
+        assert_eq!(
+            render_topic("{device_id}/{sensor}", &VARS).unwrap(),
+            "a1b2c3/soil_moisture"
+        );
+        assert_eq!(render_topic("plants", &VARS).unwrap(), "plants");
+    }
+
+    #[test]
+    fn plant_name_becomes_a_single_topic_level() {
+        assert_eq!(
+            topic_segment(" Ficus/Benjamin #2 + 3 "),
+            "ficus_benjamin__2___3"
+        );
+        assert_eq!(topic_segment("Aloe\tVera"), "aloe_vera");
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert_eq!(
+            render_topic("plants/{foo}", &VARS),
+            Err(TopicError::UnknownPlaceholder("foo".to_string()))
+        );
+        assert_eq!(
+            render_topic("plants/{sensor", &VARS),
+            Err(TopicError::UnclosedPlaceholder)
+        );
+        assert_eq!(
+            render_topic("plants/#", &VARS),
+            Err(TopicError::InvalidTopic("plants/#".to_string()))
+        );
+        assert_eq!(
+            render_topic("", &VARS),
+            Err(TopicError::InvalidTopic(String::new()))
+        );
+    }
+
+    #[test]
+    fn qos_levels() {
+        assert_eq!(qos_from_level(0), Ok(QoS::AtMostOnce));
+        assert_eq!(qos_from_level(1), Ok(QoS::AtLeastOnce));
+        assert_eq!(qos_from_level(2), Ok(QoS::ExactlyOnce));
+        assert_eq!(qos_from_level(3), Err(TopicError::InvalidQos(3)));
+    }
+}
@@ -0,0 +1,111 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::clock::timestamp::Timestamp;
+use crate::sensor::Reading;
+
+/// All readings of one sensor loop cycle, published together on `<base>/state`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateMessage {
+    /// Increments with every state message since boot.
+    pub seq: u64,
+    /// `None` while the clock is not synchronized.
+    pub timestamp: Option<Timestamp>,
+    pub firmware_version: &'static str,
+    /// Wi-Fi signal strength in dBm.
+    pub rssi: Option<i8>,
+    pub readings: Vec<Reading>,
+}
+
+#[derive(Serialize)]
+struct StatePayload<'a> {
+    seq: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    firmware_version: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rssi: Option<i8>,
+    readings: Map<String, Value>,
+}
+
+impl StateMessage {
+    /// Renders e.g. `{"seq": 7, "timestamp": "...", "firmware_version": "0.1.0",
+    /// "rssi": -61, "readings": {"soil_moisture": {"value": 42.0, "unit": "%"}}}`.
+    pub fn to_json(&self) -> String {
+        let readings = self
+            .readings
+            .iter()
+            .map(|reading| {
+                let value = json!({
+                    "value": reading.value,
+                    "unit": reading.unit(),
+                });
+                (reading.quantity.key().to_string(), value)
+            })
+            .collect();
+
+        let payload = StatePayload {
+            seq: self.seq,
+            timestamp: self.timestamp.map(|timestamp| timestamp.to_iso8601()),
+            firmware_version: self.firmware_version,
+            rssi: self.rssi,
+            readings,
+        };
+
+        serde_json::to_string(&payload).expect("State payload is serializable")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensor::Quantity;
+
+    fn reading(quantity: Quantity, value: f32) -> Reading {
+        Reading {
+            quantity,
+            value,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn renders_readings_by_key() {
+        let message = StateMessage {
+            seq: 7,
+            timestamp: Some(Timestamp::from_unix_millis(1_709_209_800_250)),
+            firmware_version: "0.1.0",
+            rssi: Some(-61),
+            readings: vec![
+                reading(Quantity::SoilMoisture, 42.0),
+                reading(Quantity::AirTemperature, 21.5),
+            ],
+        };
+
+        // The discovery value templates read `value_json.readings.<key>.value`.
+        assert_eq!(
+            message.to_json(),
+            concat!(
+                r#"{"seq":7,"timestamp":"2024-02-29T12:30:00.250Z","firmware_version":"0.1.0","#,
+                r#""rssi":-61,"readings":{"air_temperature":{"unit":"°C","value":21.5},"#,
+                r#""soil_moisture":{"unit":"%","value":42.0}}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn omits_the_unknown_timestamp_and_rssi() {
+        let message = StateMessage {
+            seq: 0,
+            timestamp: None,
+            firmware_version: "0.1.0",
+            rssi: None,
+            readings: Vec::new(),
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"seq":0,"firmware_version":"0.1.0","readings":{}}"#
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use super::sensor_config::SensorConfig;
+use super::state::StateMessage;
+use super::{PublishError, Publisher};
+use crate::health::status::ConnectivityStatus;
+use crate::sensor::{Quantity, Reading};
+
+/// Stands in for a backend that could not be started, e.g. MQTT without a
+/// broker. Everything fails, so the backend shows up as failing in the log
+/// and metrics while the other backends keep working.
+pub struct UnavailablePublisher;
+
+impl Publisher for UnavailablePublisher {
+    fn publish(&mut self, _config: &SensorConfig, _reading: &Reading) -> Result<(), PublishError> {
+        Err(PublishError::NotConnected)
+    }
+
+    fn publish_state(&mut self, _state: &StateMessage) -> Result<(), PublishError> {
+        Err(PublishError::NotConnected)
+    }
+
+    fn publish_connectivity(
+        &mut self,
+        _status: &ConnectivityStatus,
+        _consecutive_failures: u32,
+    ) -> Result<(), PublishError> {
+        Err(PublishError::NotConnected)
+    }
+
+    fn publish_plant_name(&mut self, _name: &str) -> Result<(), PublishError> {
+        Err(PublishError::NotConnected)
+    }
+
+    fn publish_availability(
+        &mut self,
+        _config: &SensorConfig,
+        _quantity: Quantity,
+        _available: bool,
+    ) -> Result<(), PublishError> {
+        Err(PublishError::NotConnected)
+    }
+}
@@ -4,7 +4,7 @@ use embedded_hal::{
     digital::{InputPin, OutputPin},
 };
 
-use super::Sensor;
+use super::{Quantity, Reading, Sensor, SensorError};
 
 pub struct AirSensor<PIN: InputPin + OutputPin, DELAY: DelayNs> {
     dht22: Dht22<PIN, DELAY>,
@@ -25,14 +25,23 @@ where
     PIN: InputPin + OutputPin,
     DELAY: DelayNs,
 {
-    fn read(&mut self) -> Vec<f32> {
-        log::info!("AHOJDA");
-        match self.dht22.read() {
-            Ok(result) => vec![result.temperature as f32, result.humidity as f32],
-            Err(e) => {
-                log::error!("Failed to read DHT22: {:?}", e);
-                return vec![];
-            }
-        }
+    fn name(&self) -> &'static str {
+        "dht22"
+    }
+
+    fn quantities(&self) -> &'static [Quantity] {
+        &[Quantity::AirTemperature, Quantity::AirHumidity]
+    }
+
+    fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
+        let result = self
+            .dht22
+            .read()
+            .map_err(|e| SensorError::Communication(format!("DHT22: {:?}", e)))?;
+
+        Ok(vec![
+            Reading::now(Quantity::AirTemperature, result.temperature),
+            Reading::now(Quantity::AirHumidity, result.humidity),
+        ])
     }
 }
@@ -1,7 +1,7 @@
 use bh1750::BH1750;
 use embedded_hal::{delay::DelayNs, i2c::I2c};
 
-use super::Sensor;
+use super::{Quantity, Reading, Sensor, SensorError};
 
 pub struct LightIntensitySensor<I2C: I2c, DELAY: DelayNs> {
     bh1750: BH1750<I2C, DELAY>,
@@ -22,20 +22,21 @@ where
     I2C: I2c,
     DELAY: DelayNs,
 {
-    fn read(&mut self) -> Vec<f32> {
-        // Attempt to read light intensity
-        match self
+    fn name(&self) -> &'static str {
+        "bh1750"
+    }
+
+    fn quantities(&self) -> &'static [Quantity] {
+        &[Quantity::LightIntensity]
+    }
+
+    fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
+        let lux = self
             .bh1750
             .get_one_time_measurement(bh1750::Resolution::High)
-        {
-            Ok(lux) => {
-                log::info!("Light intensity: {} lux", lux);
-                vec![lux as f32]
-            }
-            Err(e) => {
-                log::error!("Error reading light intensity: {:?}", e);
-                vec![0.0]
-            }
-        }
+            .map_err(|e| SensorError::Communication(format!("BH1750: {:?}", e)))?;
+        log::info!("Light intensity: {} lux", lux);
+
+        Ok(vec![Reading::now(Quantity::LightIntensity, lux)])
     }
 }
@@ -1,15 +1,153 @@
 pub mod air_sensor;
 pub mod light_intensity_sensor;
+#[cfg(target_os = "espidf")]
 pub mod soil_humidity_sensor;
 pub mod test_light_intensity_sensor;
 pub mod test_soil_moisture_sensor;
 
+use std::fmt;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::clock::timestamp::Timestamp;
+
 pub trait Sensor {
-    fn read(&mut self) -> Vec<f32>;
+    /// Identifies the sensor in logs and metrics, e.g. `bh1750`.
+    fn name(&self) -> &'static str;
+
+    /// Quantities returned by [`Sensor::read`].
+    fn quantities(&self) -> &'static [Quantity];
+
+    /// Measures all quantities of the sensor.
+    fn read(&mut self) -> Result<Vec<Reading>, SensorError>;
+
+    /// Takes the current raw measurement as the given calibration point and returns it.
+    fn calibrate(&mut self, _point: CalibrationPoint) -> Result<i16, String> {
+        Err("sensor cannot be calibrated".to_string())
+    }
+}
+
+/// Reads the sensor, trying again up to `retries` times with `delay` in between.
+/// `on_error` is called for every failed attempt.
+pub fn read_with_retries(
+    sensor: &mut dyn Sensor,
+    retries: u32,
+    delay: Duration,
+    mut on_error: impl FnMut(&SensorError),
+) -> Result<Vec<Reading>, SensorError> {
+    let mut attempt = 0;
+
+    loop {
+        match sensor.read() {
+            Ok(readings) => return Ok(readings),
+            Err(e) => {
+                on_error(&e);
+                if attempt >= retries {
+                    return Err(e);
+                }
+                attempt += 1;
+                thread::sleep(delay);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationPoint {
+    Dry,
+    Wet,
+}
+
+/// What a reading measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Quantity {
+    SoilMoisture,
+    LightIntensity,
+    AirTemperature,
+    AirHumidity,
+}
+
+impl Quantity {
+    pub const ALL: [Quantity; 4] = [
+        Quantity::SoilMoisture,
+        Quantity::LightIntensity,
+        Quantity::AirTemperature,
+        Quantity::AirHumidity,
+    ];
+
+    /// Stable identifier, e.g. for persisted readings.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Quantity::SoilMoisture => "soil_moisture",
+            Quantity::LightIntensity => "light_intensity",
+            Quantity::AirTemperature => "air_temperature",
+            Quantity::AirHumidity => "air_humidity",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Quantity::SoilMoisture => "%",
+            Quantity::LightIntensity => "lx",
+            Quantity::AirTemperature => "°C",
+            Quantity::AirHumidity => "%",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|quantity| quantity.key() == key)
+    }
+}
+
+/// One value measured by a sensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub quantity: Quantity,
+    pub value: f32,
+    /// `None` while the clock is not synchronized.
+    pub timestamp: Option<Timestamp>,
+}
+
+impl Reading {
+    /// A value measured right now.
+    pub fn now(quantity: Quantity, value: f32) -> Self {
+        Self {
+            quantity,
+            value,
+            timestamp: Timestamp::from_system_time(SystemTime::now()),
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        self.quantity.unit()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SensorError {
+    /// The sensor did not answer or the bus transfer failed.
+    Communication(String),
 }
 
-pub enum SensorType {
-    AirSensor,
-    LightIntensitySensor,
-    SoilHumiditySensor,
+impl fmt::Display for SensorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorError::Communication(e) => write!(f, "communication failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SensorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_round_trip() {
+        for quantity in Quantity::ALL {
+            assert_eq!(Quantity::from_key(quantity.key()), Some(quantity));
+        }
+        assert_eq!(Quantity::from_key("humidity"), None);
+    }
 }
@@ -1,16 +1,13 @@
 use std::borrow::Borrow;
-use std::rc::Rc;
 
-use esp_idf_hal::adc::attenuation::DB_11;
-use esp_idf_hal::adc::oneshot::config::AdcChannelConfig;
 use esp_idf_hal::adc::oneshot::AdcChannelDriver;
-use esp_idf_hal::adc::{oneshot::*, ADC1};
-use esp_idf_hal::gpio::{ADCPin, Gpio34, Pin};
-use esp_idf_hal::prelude::Peripherals;
+use esp_idf_hal::adc::oneshot::*;
+use esp_idf_hal::gpio::ADCPin;
+use esp_idf_hal::sys::EspError;
 
-use crate::config::AppConfig;
+use crate::config::CalibrationConfig;
 
-use super::Sensor;
+use super::{CalibrationPoint, Quantity, Reading, Sensor, SensorError};
 
 pub struct SoilMoistureSensor<'a, A, P>
 where
@@ -19,8 +16,7 @@ where
 {
     adc: A,
     adc_pin: AdcChannelDriver<'a, P, A>,
-    wet_value: i16,
-    dry_value: i16,
+    calibration: CalibrationConfig,
 }
 
 impl<'a, A, P> SoilMoistureSensor<'a, A, P>
@@ -37,10 +33,16 @@ where
         Self {
             adc,
             adc_pin,
-            wet_value,
-            dry_value,
+            calibration: CalibrationConfig {
+                wet_value,
+                dry_value,
+            },
         }
     }
+
+    fn read_adc(&mut self) -> Result<i16, EspError> {
+        Ok(self.adc.borrow().read(&mut self.adc_pin)? as i16)
+    }
 }
 
 impl<'a, A, P> Sensor for SoilMoistureSensor<'a, A, P>
@@ -48,16 +50,32 @@ where
     A: Borrow<AdcDriver<'a, P::Adc>>,
     P: ADCPin,
 {
-    fn read(&mut self) -> Vec<f32> {
-        let adc_value = self.adc.borrow().read(&mut self.adc_pin).unwrap() as i16;
+    fn name(&self) -> &'static str {
+        "soil_moisture"
+    }
+
+    fn quantities(&self) -> &'static [Quantity] {
+        &[Quantity::SoilMoisture]
+    }
+
+    fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
+        let adc_value = self
+            .read_adc()
+            .map_err(|e| SensorError::Communication(format!("ADC: {}", e)))?;
         log::info!("ADC Value: {}", adc_value);
-        let percentage = ((self.dry_value - adc_value) as f32
-            / (self.dry_value - self.wet_value) as f32)
-            * 100.0;
+        let percentage = self.calibration.moisture_percentage(adc_value);
+
+        Ok(vec![Reading::now(Quantity::SoilMoisture, percentage)])
+    }
+
+    fn calibrate(&mut self, point: CalibrationPoint) -> Result<i16, String> {
+        let adc_value = self
+            .read_adc()
+            .map_err(|e| format!("failed to read ADC: {}", e))?;
 
-        // cap the percentage to 0-100
-        let percentage = percentage.max(0.0).min(100.0);
+        self.calibration = self.calibration.with_point(point, adc_value)?;
+        log::info!("Calibrated {:?} point to ADC value {}", point, adc_value);
 
-        vec![percentage]
+        Ok(adc_value)
     }
 }
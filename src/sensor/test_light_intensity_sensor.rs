@@ -1,6 +1,7 @@
-use super::Sensor;
+use super::{Quantity, Reading, Sensor, SensorError};
 use rand;
 
+#[derive(Default)]
 pub struct TestLightIntensitySensor {}
 
 impl TestLightIntensitySensor {
@@ -10,10 +11,18 @@ impl TestLightIntensitySensor {
 }
 
 impl Sensor for TestLightIntensitySensor {
-    fn read(&mut self) -> Vec<f32> {
+    fn name(&self) -> &'static str {
+        "test_light_intensity"
+    }
+
+    fn quantities(&self) -> &'static [Quantity] {
+        &[Quantity::LightIntensity]
+    }
+
+    fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
         // generate random value between 100 and 300
         let value = rand::random::<f32>() * 200.0 + 100.0;
         log::info!("Light intensity: {}%", value);
-        vec![value]
+        Ok(vec![Reading::now(Quantity::LightIntensity, value)])
     }
 }
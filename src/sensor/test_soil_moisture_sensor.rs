@@ -1,6 +1,7 @@
-use super::Sensor;
+use super::{Quantity, Reading, Sensor, SensorError};
 use rand;
 
+#[derive(Default)]
 pub struct TestSoilMoistureSensor {}
 
 impl TestSoilMoistureSensor {
@@ -10,10 +11,18 @@ impl TestSoilMoistureSensor {
 }
 
 impl Sensor for TestSoilMoistureSensor {
-    fn read(&mut self) -> Vec<f32> {
+    fn name(&self) -> &'static str {
+        "test_soil_moisture"
+    }
+
+    fn quantities(&self) -> &'static [Quantity] {
+        &[Quantity::SoilMoisture]
+    }
+
+    fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
         // generate random value between 0 and 100
         let value = rand::random::<f32>() * 100.0;
         log::info!("Soil moisture: {}%", value);
-        vec![value]
+        Ok(vec![Reading::now(Quantity::SoilMoisture, value)])
     }
 }
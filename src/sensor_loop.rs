@@ -0,0 +1,354 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, Instant};
+
+use crate::clock::timestamp::Timestamp;
+use crate::device;
+use crate::metrics::Metrics;
+use crate::publisher::change_filter::ChangeFilter;
+use crate::publisher::sensor_config::SensorConfig;
+use crate::publisher::state::StateMessage;
+use crate::publisher::Publisher;
+use crate::sensor::{read_with_retries, Quantity, Reading, Sensor};
+
+/// A sensor with the publish settings of each quantity it measures.
+pub type SensorItem = (Box<dyn Sensor>, Vec<(Quantity, SensorConfig)>);
+
+/// How the sensors are read, from `[sensors]`.
+pub struct ReadSettings {
+    /// Publish all readings of a cycle as one state message.
+    pub batched: bool,
+    pub read_retries: u32,
+    pub retry_delay: Duration,
+}
+
+/// What a reading cycle needs from the rest of the device.
+pub struct CycleInput {
+    /// Report every reading, even if the change filter would skip it.
+    pub report_all: bool,
+    /// Time of the state message, `None` while the clock is not synchronized.
+    pub timestamp: Option<Timestamp>,
+    /// Wi-Fi signal strength in dBm, `None` while disconnected.
+    pub rssi: Option<i8>,
+}
+
+/// Reads the sensors once per cycle and publishes what changed, keeping the
+/// state that carries over from one cycle to the next.
+pub struct SensorLoop {
+    settings: ReadSettings,
+    state_seq: u64,
+    latest_readings: BTreeMap<Quantity, Reading>,
+    unavailable: BTreeSet<Quantity>,
+    /// Availability last published per quantity, announced again when it changes.
+    published_availability: BTreeMap<Quantity, bool>,
+    change_filters: BTreeMap<Quantity, ChangeFilter>,
+}
+
+impl SensorLoop {
+    pub fn new(settings: ReadSettings, sensors: &[SensorItem]) -> Self {
+        let change_filters = sensors
+            .iter()
+            .flat_map(|(_, configs)| configs)
+            .map(|(quantity, config)| (*quantity, ChangeFilter::new(config.report)))
+            .collect();
+
+        Self {
+            settings,
+            state_seq: 0,
+            latest_readings: BTreeMap::new(),
+            unavailable: BTreeSet::new(),
+            published_availability: BTreeMap::new(),
+            change_filters,
+        }
+    }
+
+    /// Latest value of every quantity that is currently available.
+    pub fn latest_readings(&self) -> Vec<Reading> {
+        self.latest_readings.values().cloned().collect()
+    }
+
+    /// Quantities whose sensor could not be read in the last cycle.
+    pub fn unavailable(&self) -> Vec<Quantity> {
+        self.unavailable.iter().copied().collect()
+    }
+
+    /// Reads every sensor and publishes the readings worth reporting.
+    pub fn run_cycle(
+        &mut self,
+        sensors: &mut [SensorItem],
+        publisher: &mut impl Publisher,
+        metrics: &Metrics,
+        input: CycleInput,
+    ) {
+        let mut state_readings = Vec::new();
+        let mut report_state = input.report_all;
+
+        for (sensor, configs) in sensors.iter_mut() {
+            log::info!("Reading sensor values");
+            let name = sensor.name();
+            let result = read_with_retries(
+                sensor.as_mut(),
+                self.settings.read_retries,
+                self.settings.retry_delay,
+                |e| {
+                    log::warn!("Error reading {}: {}", name, e);
+                    metrics.record_read_error(name);
+                },
+            );
+            metrics.record_sensor_available(name, result.is_ok());
+            let readings = result.unwrap_or_else(|e| {
+                log::error!("Sensor {} unavailable: {}", name, e);
+                Vec::new()
+            });
+
+            for (quantity, config) in configs.iter() {
+                let available = readings.iter().any(|reading| reading.quantity == *quantity);
+                if available {
+                    self.unavailable.remove(quantity);
+                } else {
+                    self.unavailable.insert(*quantity);
+                    self.latest_readings.remove(quantity);
+                    metrics.clear_reading(*quantity);
+                }
+
+                if self.published_availability.get(quantity) == Some(&available) {
+                    continue;
+                }
+                match publisher.publish_availability(config, *quantity, available) {
+                    Ok(()) => {
+                        self.published_availability.insert(*quantity, available);
+                    }
+                    Err(e) => log::error!("Error publishing availability: {}", e),
+                }
+            }
+
+            for reading in readings {
+                metrics.record_reading(reading.quantity, reading.value);
+                self.latest_readings
+                    .insert(reading.quantity, reading.clone());
+
+                let Some((_, config)) = configs
+                    .iter()
+                    .find(|(quantity, _)| *quantity == reading.quantity)
+                else {
+                    log::warn!("No publish settings for {:?}", reading.quantity);
+                    continue;
+                };
+                let filter = self
+                    .change_filters
+                    .entry(reading.quantity)
+                    .or_insert_with(|| ChangeFilter::new(config.report));
+
+                let now = Instant::now();
+                if self.settings.batched {
+                    report_state |= filter.should_report(reading.value, now);
+                    state_readings.push(reading);
+                    continue;
+                }
+                if !input.report_all && !filter.should_report(reading.value, now) {
+                    continue;
+                }
+                // A failed publish is buffered, so the value counts as reported either way
+                filter.record(reading.value, now);
+
+                if let Err(e) = publisher.publish(config, &reading) {
+                    log::error!("Error publishing {}: {}", config.topic, e);
+                }
+            }
+        }
+
+        if report_state && !state_readings.is_empty() {
+            // Every reading is part of the state, so all of them count as reported
+            let now = Instant::now();
+            for reading in &state_readings {
+                if let Some(filter) = self.change_filters.get_mut(&reading.quantity) {
+                    filter.record(reading.value, now);
+                }
+            }
+
+            self.state_seq += 1;
+            let state = StateMessage {
+                seq: self.state_seq,
+                timestamp: input.timestamp,
+                firmware_version: device::FIRMWARE_VERSION,
+                rssi: input.rssi,
+                readings: state_readings,
+            };
+
+            if let Err(e) = publisher.publish_state(&state) {
+                log::error!("Error publishing state: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publisher::change_filter::{Deadband, ReportPolicy};
+    use crate::publisher::memory_publisher::MemoryPublisher;
+    use crate::publisher::sensor_config::QoS;
+    use crate::sensor::SensorError;
+
+    /// Returns the queued values one per read, failing once they run out.
+    struct ScriptedSensor {
+        values: Vec<f32>,
+    }
+
+    impl Sensor for ScriptedSensor {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn quantities(&self) -> &'static [Quantity] {
+            &[Quantity::SoilMoisture]
+        }
+
+        fn read(&mut self) -> Result<Vec<Reading>, SensorError> {
+            if self.values.is_empty() {
+                return Err(SensorError::Communication("no answer".to_string()));
+            }
+            let value = self.values.remove(0);
+            Ok(vec![Reading::now(Quantity::SoilMoisture, value)])
+        }
+    }
+
+    fn sensors(values: &[f32]) -> Vec<SensorItem> {
+        let config = SensorConfig {
+            topic: "plant/soil_moisture".to_string(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+            report: ReportPolicy {
+                deadband: Deadband::Absolute(1.0),
+                heartbeat: Duration::from_secs(3600),
+            },
+        };
+        let sensor = ScriptedSensor {
+            values: values.to_vec(),
+        };
+
+        vec![(Box::new(sensor), vec![(Quantity::SoilMoisture, config)])]
+    }
+
+    fn sensor_loop(batched: bool, sensors: &[SensorItem]) -> SensorLoop {
+        let settings = ReadSettings {
+            batched,
+            read_retries: 0,
+            retry_delay: Duration::ZERO,
+        };
+        SensorLoop::new(settings, sensors)
+    }
+
+    fn metrics() -> Metrics {
+        Metrics::new(Quantity::ALL, ["scripted"], ["memory"])
+    }
+
+    fn input() -> CycleInput {
+        CycleInput {
+            report_all: false,
+            timestamp: None,
+            rssi: Some(-60),
+        }
+    }
+
+    fn published_values(publisher: &MemoryPublisher) -> Vec<f32> {
+        publisher
+            .readings
+            .iter()
+            .map(|(_, reading)| reading.value)
+            .collect()
+    }
+
+    #[test]
+    fn publishes_only_readings_leaving_the_deadband() {
+        let mut sensors = sensors(&[40.0, 40.5, 42.0]);
+        let mut sensor_loop = sensor_loop(false, &sensors);
+        let mut publisher = MemoryPublisher::new();
+        let metrics = metrics();
+
+        for _ in 0..3 {
+            sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+        }
+
+        assert_eq!(published_values(&publisher), vec![40.0, 42.0]);
+        assert_eq!(publisher.readings[0].0, "plant/soil_moisture");
+        assert_eq!(publisher.availability, vec![(Quantity::SoilMoisture, true)]);
+        assert_eq!(sensor_loop.latest_readings()[0].value, 42.0);
+    }
+
+    #[test]
+    fn report_all_bypasses_the_deadband() {
+        let mut sensors = sensors(&[40.0, 40.0]);
+        let mut sensor_loop = sensor_loop(false, &sensors);
+        let mut publisher = MemoryPublisher::new();
+        let metrics = metrics();
+
+        sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+        let report_all = CycleInput {
+            report_all: true,
+            ..input()
+        };
+        sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, report_all);
+
+        assert_eq!(published_values(&publisher), vec![40.0, 40.0]);
+    }
+
+    #[test]
+    fn failing_sensor_becomes_unavailable() {
+        let mut sensors = sensors(&[40.0]);
+        let mut sensor_loop = sensor_loop(false, &sensors);
+        let mut publisher = MemoryPublisher::new();
+        let metrics = metrics();
+
+        sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+        sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+
+        assert_eq!(
+            publisher.availability,
+            vec![
+                (Quantity::SoilMoisture, true),
+                (Quantity::SoilMoisture, false)
+            ]
+        );
+        assert!(sensor_loop.latest_readings().is_empty());
+        assert_eq!(sensor_loop.unavailable(), vec![Quantity::SoilMoisture]);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.sensors["scripted"].read_errors, 1);
+        assert_eq!(snapshot.quantities["soil_moisture"].value, None);
+    }
+
+    #[test]
+    fn availability_is_published_again_after_a_failure() {
+        let mut sensors = sensors(&[40.0, 40.0]);
+        let mut sensor_loop = sensor_loop(false, &sensors);
+        let mut publisher = MemoryPublisher {
+            offline: true,
+            ..MemoryPublisher::new()
+        };
+        let metrics = metrics();
+
+        sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+        publisher.offline = false;
+        sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+
+        assert_eq!(publisher.availability, vec![(Quantity::SoilMoisture, true)]);
+    }
+
+    #[test]
+    fn batched_mode_publishes_one_state_per_change() {
+        let mut sensors = sensors(&[40.0, 40.5, 42.0]);
+        let mut sensor_loop = sensor_loop(true, &sensors);
+        let mut publisher = MemoryPublisher::new();
+        let metrics = metrics();
+
+        for _ in 0..3 {
+            sensor_loop.run_cycle(&mut sensors, &mut publisher, &metrics, input());
+        }
+
+        assert!(publisher.readings.is_empty());
+        let seqs: Vec<u64> = publisher.states.iter().map(|state| state.seq).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        let last = &publisher.states[1];
+        assert_eq!(last.rssi, Some(-60));
+        assert_eq!(last.readings[0].value, 42.0);
+    }
+}
@@ -0,0 +1,29 @@
+#[cfg(target_os = "espidf")]
+use std::ffi::CString;
+
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::sys::{esp, esp_vfs_spiffs_conf_t, esp_vfs_spiffs_register, EspError};
+
+pub const MOUNT_POINT: &str = "/spiffs";
+
+/// Mounts the `storage` SPIFFS partition (see `partitions.csv`) at [`MOUNT_POINT`].
+#[cfg(target_os = "espidf")]
+pub fn mount() -> Result<(), EspError> {
+    let base_path = CString::new(MOUNT_POINT).expect("Mount point contains no NUL byte");
+    let conf = esp_vfs_spiffs_conf_t {
+        base_path: base_path.as_ptr(),
+        partition_label: c"storage".as_ptr(),
+        max_files: 5,
+        format_if_mount_failed: false,
+    };
+
+    esp!(unsafe { esp_vfs_spiffs_register(&conf) })?;
+
+    log::info!("Mounted storage partition at {}", MOUNT_POINT);
+    Ok(())
+}
+
+/// Returns the absolute path of a file on the storage partition.
+pub fn path(file_name: &str) -> String {
+    format!("{}/{}", MOUNT_POINT, file_name)
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use esp_idf_hal::peripheral::Peripheral;
+use esp_idf_hal::sys::{
+    esp, esp_wifi_sta_get_ap_info, wifi_ap_record_t, EspError, ESP_ERR_INVALID_ARG,
+};
+use esp_idf_svc::eventloop::EspSystemEventLoop;
+use esp_idf_svc::ipv4;
+use esp_idf_svc::netif::{EspNetif, NetifConfiguration, NetifStack};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::wifi::*;
+
+use crate::config::StaticIpConfig;
+
+/// Creates the Wi-Fi driver with a station interface using `hostname` and
+/// either DHCP or the given static IPv4 settings.
+pub fn create_wifi(
+    modem: impl Peripheral<P = esp_idf_svc::hal::modem::Modem> + 'static,
+    nvs: EspDefaultNvsPartition,
+    hostname: &str,
+    static_ip: Option<&StaticIpConfig>,
+) -> Result<EspWifi<'static>, EspError> {
+    let sysloop = EspSystemEventLoop::take()?;
+
+    log::info!("Creating Wi-Fi instance");
+    let driver = WifiDriver::new(modem, sysloop, Some(nvs))?;
+
+    let ip_configuration = match static_ip {
+        Some(static_ip) => {
+            log::info!("Using static IP address {}", static_ip.address);
+            ipv4::ClientConfiguration::Fixed(static_ip_settings(static_ip)?)
+        }
+        None => ipv4::ClientConfiguration::DHCP(ipv4::DHCPClientSettings {
+            hostname: Some(
+                heapless::String::from_str(hostname)
+                    .map_err(|_| EspError::from_infallible::<ESP_ERR_INVALID_ARG>())?,
+            ),
+        }),
+    };
+
+    let mut sta_netif = EspNetif::new_with_conf(&NetifConfiguration {
+        ip_configuration: Some(ipv4::Configuration::Client(ip_configuration)),
+        ..NetifConfiguration::wifi_default_client()
+    })?;
+    sta_netif.set_hostname(hostname)?;
+    log::info!("Using hostname {}", hostname);
+
+    EspWifi::wrap_all(driver, sta_netif, EspNetif::new(NetifStack::Ap)?)
+}
+
+fn static_ip_settings(config: &StaticIpConfig) -> Result<ipv4::ClientSettings, EspError> {
+    let Some(prefix_len) = config.prefix_len() else {
+        log::error!("Invalid netmask {}", config.netmask);
+        return Err(EspError::from_infallible::<ESP_ERR_INVALID_ARG>());
+    };
+
+    if config.dns.len() > 2 {
+        log::warn!("Only the first two DNS servers are used");
+    }
+
+    Ok(ipv4::ClientSettings {
+        ip: config.address,
+        subnet: ipv4::Subnet {
+            gateway: config.gateway,
+            mask: ipv4::Mask(prefix_len),
+        },
+        dns: config.dns.first().copied(),
+        secondary_dns: config.dns.get(1).copied(),
+    })
+}
+
+pub fn configure_client(wifi: &mut EspWifi, ssid: &str, password: &str) -> Result<(), EspError> {
+    log::info!("Setting Wi-Fi configuration for network: {}", ssid);
+    wifi.set_configuration(&Configuration::Client(ClientConfiguration {
+        ssid: heapless::String::from_str(ssid)
+            .map_err(|_| EspError::from(1).expect("Failed to create string"))?,
+        password: heapless::String::from_str(password)
+            .map_err(|_| EspError::from(1).expect("Failed to create string"))?,
+        ..Default::default()
+    }))
+}
+
+/// Signal strength of the access point the station is connected to, in dBm.
+pub fn rssi() -> Result<i8, EspError> {
+    let mut ap_info = wifi_ap_record_t::default();
+    esp!(unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) })?;
+
+    Ok(ap_info.rssi)
+}
@@ -0,0 +1,10 @@
+pub mod policy;
+pub mod selection;
+#[cfg(target_os = "espidf")]
+pub mod supervisor;
+
+#[cfg(target_os = "espidf")]
+mod driver;
+
+#[cfg(target_os = "espidf")]
+pub use driver::{configure_client, create_wifi, rssi};
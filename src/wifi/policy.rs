@@ -0,0 +1,197 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WifiState {
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+/// What the supervisor should do with the Wi-Fi driver after a [`ReconnectPolicy::poll`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Connect,
+    /// Abort the current connection attempt, it took too long.
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BackoffConfig {
+    pub connect_timeout: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(15),
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Phase {
+    Idle { retry_at: Instant },
+    Connecting { started: Instant },
+    Connected,
+}
+
+/// Reconnect state machine with a connection timeout and exponential backoff.
+///
+/// It does not touch the driver nor read the clock itself, the caller passes the
+/// current time and link state in and acts on the returned [`Action`].
+pub struct ReconnectPolicy {
+    config: BackoffConfig,
+    phase: Phase,
+    failures: u32,
+}
+
+impl ReconnectPolicy {
+    /// Creates a policy that wants to connect right away.
+    pub fn new(config: BackoffConfig, now: Instant) -> Self {
+        Self {
+            config,
+            phase: Phase::Idle { retry_at: now },
+            failures: 0,
+        }
+    }
+
+    pub fn state(&self) -> WifiState {
+        match self.phase {
+            Phase::Idle { .. } => WifiState::Disconnected,
+            Phase::Connecting { .. } => WifiState::Connecting,
+            Phase::Connected => WifiState::Connected,
+        }
+    }
+
+    /// Number of failed attempts since the last successful connection.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub fn poll(&mut self, now: Instant, link_up: bool) -> Action {
+        match self.phase {
+            Phase::Connected if link_up => Action::None,
+            Phase::Connected => {
+                // The link dropped, try to get it back immediately
+                self.phase = Phase::Connecting { started: now };
+                Action::Connect
+            }
+            Phase::Connecting { .. } | Phase::Idle { .. } if link_up => {
+                self.phase = Phase::Connected;
+                self.failures = 0;
+                Action::None
+            }
+            Phase::Connecting { started } => {
+                if now.saturating_duration_since(started) < self.config.connect_timeout {
+                    return Action::None;
+                }
+
+                self.failures += 1;
+                self.phase = Phase::Idle {
+                    retry_at: now + self.backoff(),
+                };
+                Action::Disconnect
+            }
+            Phase::Idle { retry_at } if now >= retry_at => {
+                self.phase = Phase::Connecting { started: now };
+                Action::Connect
+            }
+            Phase::Idle { .. } => Action::None,
+        }
+    }
+
+    /// Delay before the next attempt, doubling with every failure up to `max_backoff`.
+    fn backoff(&self) -> Duration {
+        let exponent = self.failures.saturating_sub(1).min(16);
+        self.config
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.config.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn config() -> BackoffConfig {
+        BackoffConfig {
+            connect_timeout: 10 * SECOND,
+            initial_backoff: 2 * SECOND,
+            max_backoff: 5 * SECOND,
+        }
+    }
+
+    /// Starts a connection attempt at `now` and lets it time out.
+    fn fail_attempt(policy: &mut ReconnectPolicy, now: Instant) -> Instant {
+        assert_eq!(policy.poll(now, false), Action::Connect);
+        let timeout = now + config().connect_timeout;
+        assert_eq!(policy.poll(timeout, false), Action::Disconnect);
+        timeout
+    }
+
+    #[test]
+    fn connects_right_away() {
+        let start = Instant::now();
+        let mut policy = ReconnectPolicy::new(config(), start);
+
+        assert_eq!(policy.state(), WifiState::Disconnected);
+        assert_eq!(policy.poll(start, false), Action::Connect);
+        assert_eq!(policy.state(), WifiState::Connecting);
+        assert_eq!(policy.poll(start + SECOND, true), Action::None);
+        assert_eq!(policy.state(), WifiState::Connected);
+    }
+
+    #[test]
+    fn gives_up_on_attempt_after_timeout() {
+        let start = Instant::now();
+        let mut policy = ReconnectPolicy::new(config(), start);
+
+        assert_eq!(policy.poll(start, false), Action::Connect);
+        assert_eq!(policy.poll(start + 9 * SECOND, false), Action::None);
+        assert_eq!(policy.poll(start + 10 * SECOND, false), Action::Disconnect);
+        assert_eq!(policy.state(), WifiState::Disconnected);
+        assert_eq!(policy.failures(), 1);
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_max() {
+        let mut policy = ReconnectPolicy::new(config(), Instant::now());
+        let mut now = Instant::now();
+
+        for expected in [2, 4, 5, 5] {
+            now = fail_attempt(&mut policy, now);
+            let backoff = expected * SECOND;
+            assert_eq!(policy.poll(now + backoff - SECOND, false), Action::None);
+            now += backoff;
+        }
+        assert_eq!(policy.failures(), 4);
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let mut policy = ReconnectPolicy::new(config(), Instant::now());
+        let now = fail_attempt(&mut policy, Instant::now());
+
+        assert_eq!(policy.poll(now, true), Action::None);
+        assert_eq!(policy.failures(), 0);
+        assert_eq!(policy.state(), WifiState::Connected);
+    }
+
+    #[test]
+    fn reconnects_immediately_when_link_drops() {
+        let start = Instant::now();
+        let mut policy = ReconnectPolicy::new(config(), start);
+        policy.poll(start, true);
+
+        assert_eq!(policy.poll(start + SECOND, false), Action::Connect);
+        assert_eq!(policy.state(), WifiState::Connecting);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::config::NetworkConfig;
+
+/// Networks weaker than this are only tried after all the stronger known ones.
+pub const MIN_USABLE_RSSI: i8 = -80;
+
+/// A network found by a Wi-Fi scan.
+#[derive(Debug, Clone)]
+pub struct VisibleNetwork {
+    pub ssid: String,
+    pub rssi: i8,
+}
+
+/// Orders the known networks in which they should be tried.
+///
+/// Visible networks with a usable signal come first in the configured priority
+/// order, then visible networks with a weak signal from the strongest one. If
+/// none of the known networks is visible (e.g. hidden SSIDs), all of them are
+/// returned in priority order.
+pub fn rank_networks<'a>(
+    known: &'a [NetworkConfig],
+    visible: &[VisibleNetwork],
+) -> Vec<&'a NetworkConfig> {
+    let mut strong = Vec::new();
+    let mut weak = Vec::new();
+
+    for network in known {
+        // The same SSID can be served by several access points, take the best one
+        let rssi = visible
+            .iter()
+            .filter(|visible| visible.ssid == network.ssid)
+            .map(|visible| visible.rssi)
+            .max();
+
+        match rssi {
+            Some(rssi) if rssi >= MIN_USABLE_RSSI => strong.push(network),
+            Some(rssi) => weak.push((rssi, network)),
+            None => {}
+        }
+    }
+
+    if strong.is_empty() && weak.is_empty() {
+        return known.iter().collect();
+    }
+
+    weak.sort_by(|(a, _), (b, _)| b.cmp(a));
+    strong.extend(weak.into_iter().map(|(_, network)| network));
+
+    strong
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(ssids: &[&str]) -> Vec<NetworkConfig> {
+        ssids
+            .iter()
+            .map(|ssid| NetworkConfig {
+                ssid: ssid.to_string(),
+                password: "password".to_string(),
+            })
+            .collect()
+    }
+
+    fn scan(networks: &[(&str, i8)]) -> Vec<VisibleNetwork> {
+        networks
+            .iter()
+            .map(|(ssid, rssi)| VisibleNetwork {
+                ssid: ssid.to_string(),
+                rssi: *rssi,
+            })
+            .collect()
+    }
+
+    fn ssids<'a>(ranked: &[&'a NetworkConfig]) -> Vec<&'a str> {
+        ranked.iter().map(|network| network.ssid.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_priority_among_usable_networks() {
+        let known = known(&["home", "garage", "neighbour"]);
+        let visible = scan(&[("garage", -40), ("home", -70), ("cafe", -30)]);
+
+        assert_eq!(ssids(&rank_networks(&known, &visible)), ["home", "garage"]);
+    }
+
+    #[test]
+    fn tries_weak_networks_last_strongest_first() {
+        let known = known(&["home", "garage", "shed"]);
+        let visible = scan(&[("home", -90), ("garage", -60), ("shed", -82)]);
+
+        assert_eq!(
+            ssids(&rank_networks(&known, &visible)),
+            ["garage", "shed", "home"]
+        );
+    }
+
+    #[test]
+    fn uses_best_access_point_of_an_ssid() {
+        let known = known(&["home", "garage"]);
+        let visible = scan(&[("home", -85), ("garage", -84), ("home", -50)]);
+
+        assert_eq!(ssids(&rank_networks(&known, &visible)), ["home", "garage"]);
+    }
+
+    #[test]
+    fn falls_back_to_all_known_networks() {
+        let known = known(&["hidden", "home"]);
+
+        assert_eq!(
+            ssids(&rank_networks(&known, &scan(&[("cafe", -30)]))),
+            ["hidden", "home"]
+        );
+        assert_eq!(ssids(&rank_networks(&known, &[])), ["hidden", "home"]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use esp_idf_hal::sys::{EspError, ESP_ERR_TIMEOUT};
+use esp_idf_svc::wifi::{ClientConfiguration, Configuration, EspWifi};
+
+use super::configure_client;
+use super::policy::{Action, BackoffConfig, ReconnectPolicy, WifiState};
+use super::selection::{rank_networks, VisibleNetwork};
+use crate::config::NetworkConfig;
+
+/// Owns the Wi-Fi driver and keeps the station connected to the best known network.
+///
+/// [`WifiSupervisor::poll`] has to be called regularly, e.g. once per sensor loop cycle.
+pub struct WifiSupervisor {
+    wifi: EspWifi<'static>,
+    policy: ReconnectPolicy,
+    networks: Vec<NetworkConfig>,
+    /// Networks to try next, refilled from a scan once exhausted.
+    candidates: Vec<NetworkConfig>,
+}
+
+impl WifiSupervisor {
+    pub fn new(mut wifi: EspWifi<'static>, networks: Vec<NetworkConfig>) -> Result<Self, EspError> {
+        wifi.set_configuration(&Configuration::Client(ClientConfiguration::default()))?;
+
+        log::info!("Starting Wi-Fi");
+        wifi.start()?;
+
+        Ok(Self {
+            wifi,
+            policy: ReconnectPolicy::new(BackoffConfig::default(), Instant::now()),
+            networks,
+            candidates: Vec::new(),
+        })
+    }
+
+    pub fn state(&self) -> WifiState {
+        self.policy.state()
+    }
+
+    pub fn wifi(&self) -> &EspWifi<'static> {
+        &self.wifi
+    }
+
+    pub fn wifi_mut(&mut self) -> &mut EspWifi<'static> {
+        &mut self.wifi
+    }
+
+    pub fn poll(&mut self) -> WifiState {
+        let previous = self.policy.state();
+        let link_up = self.wifi.is_up().unwrap_or(false);
+
+        match self.policy.poll(Instant::now(), link_up) {
+            Action::Connect => {
+                if let Err(e) = self.connect_next() {
+                    log::error!("Failed to start Wi-Fi connection: {:?}", e);
+                }
+            }
+            Action::Disconnect => {
+                log::warn!("Wi-Fi connection attempt timed out");
+                if let Err(e) = self.wifi.disconnect() {
+                    log::error!("Failed to abort Wi-Fi connection: {:?}", e);
+                }
+            }
+            Action::None => {}
+        }
+
+        let state = self.policy.state();
+        if state != previous {
+            log::info!("Wi-Fi state changed: {:?} -> {:?}", previous, state);
+
+            if state == WifiState::Connected {
+                // Pick the best network again from a fresh scan once this one drops
+                self.candidates.clear();
+            }
+        }
+
+        state
+    }
+
+    /// Blocks until the station is connected or `max_failures` attempts have failed.
+    pub fn wait_connected(&mut self, max_failures: u32) -> Result<(), EspError> {
+        log::info!("Waiting for Wi-Fi connection");
+
+        while self.poll() != WifiState::Connected {
+            if self.policy.failures() >= max_failures {
+                return Err(EspError::from_infallible::<ESP_ERR_TIMEOUT>());
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        Ok(())
+    }
+
+    /// Configures the station for the next ranked candidate and starts connecting to it.
+    fn connect_next(&mut self) -> Result<(), EspError> {
+        if self.candidates.is_empty() {
+            self.candidates = self.scan_candidates();
+        }
+        if self.candidates.is_empty() {
+            log::warn!("No Wi-Fi networks configured");
+            return Ok(());
+        }
+
+        let network = self.candidates.remove(0);
+        log::info!(
+            "Connecting to Wi-Fi network {} (failed attempts: {})",
+            network.ssid,
+            self.policy.failures()
+        );
+
+        configure_client(&mut self.wifi, &network.ssid, &network.password)?;
+        self.wifi.connect()
+    }
+
+    fn scan_candidates(&mut self) -> Vec<NetworkConfig> {
+        let visible = match self.wifi.scan() {
+            Ok(access_points) => access_points
+                .into_iter()
+                .map(|access_point| VisibleNetwork {
+                    ssid: access_point.ssid.to_string(),
+                    rssi: access_point.signal_strength,
+                })
+                .collect(),
+            Err(e) => {
+                log::warn!("Wi-Fi scan failed: {:?}", e);
+                Vec::new()
+            }
+        };
+
+        for network in &visible {
+            log::info!(
+                "Found Wi-Fi network {} ({} dBm)",
+                network.ssid,
+                network.rssi
+            );
+        }
+
+        rank_networks(&self.networks, &visible)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+}